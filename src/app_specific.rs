@@ -0,0 +1,104 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::{Builder, Uuid};
+
+use crate::MachineId;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const APP_SPECIFIC_DOMAIN: &[u8] = b"yamid.app-specific.v";
+
+impl MachineId {
+    /// Derives a stable application-specific ID from the machine ID.
+    ///
+    /// The result is `HMAC-SHA256(machine_id, domain || version || app_id)` truncated to 128 bits
+    /// and stamped as a custom (version 8) UUID, so the raw machine ID cannot be recovered from it.
+    /// Equivalent to [`MachineId::app_specific_versioned`] with version `1`.
+    pub fn app_specific(&self, app_id: impl AsRef<[u8]>) -> MachineId {
+        self.app_specific_versioned(app_id, 1)
+    }
+
+    /// Same as [`MachineId::app_specific`], but with an explicit derivation version.
+    ///
+    /// Bumping the version yields an unrelated ID for the same machine and application,
+    /// which allows rotating the derived IDs without changing the application ID.
+    pub fn app_specific_versioned(&self, app_id: impl AsRef<[u8]>, version: u32) -> MachineId {
+        let digest = hmac_sha256(
+            self.0.as_bytes(),
+            &[APP_SPECIFIC_DOMAIN, &version.to_be_bytes(), app_id.as_ref()],
+        );
+
+        MachineId(Builder::from_custom_bytes(truncate(&digest)).into_uuid())
+    }
+
+    /// Derives an application-specific ID exactly like systemd's `sd_id128_get_machine_app_specific()`
+    /// and `systemd-id128 machine-id --app-specific=<app_id>` do.
+    ///
+    /// The result is `HMAC-SHA256(machine_id, app_id)` truncated to 128 bits and stamped as a UUID v4.
+    pub fn app_specific_systemd(&self, app_id: Uuid) -> MachineId {
+        MachineId(systemd_app_specific(&self.0, &app_id))
+    }
+}
+
+pub(crate) fn systemd_app_specific(base: &Uuid, app_id: &Uuid) -> Uuid {
+    let digest = hmac_sha256(base.as_bytes(), &[app_id.as_bytes()]);
+    Builder::from_random_bytes(truncate(&digest)).into_uuid()
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any size");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+pub(crate) fn truncate(digest: &[u8; 32]) -> uuid::Bytes {
+    let mut bytes = uuid::Bytes::default();
+    bytes.copy_from_slice(&digest[..16]);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: MachineId = MachineId(Uuid::from_u128(0x3d1219c7c4c5404aaa1f6d2a48adfda4));
+
+    #[test]
+    fn test_app_specific() {
+        let first = ID.app_specific("com.example.app");
+        let second = ID.app_specific("com.example.app");
+
+        assert_eq!(first, second);
+        assert_ne!(first, ID);
+        assert_ne!(first, ID.app_specific("com.example.other"));
+        assert_eq!(first, ID.app_specific_versioned("com.example.app", 1));
+        assert_ne!(first, ID.app_specific_versioned("com.example.app", 2));
+
+        let uuid: &Uuid = first.as_ref();
+        assert_eq!(uuid.get_version_num(), 8);
+        assert_eq!(uuid.get_variant(), uuid::Variant::RFC4122);
+    }
+
+    #[test]
+    fn test_app_specific_systemd() {
+        // Output of systemd 252's `systemd-id128 machine-id --app-specific=<app_id>`, with `ID`
+        // bind-mounted over `/etc/machine-id` in a private mount namespace (`unshare -m`).
+        let vectors = [
+            (
+                0xb08f2b8d3ad64b87a9c1e8a2c6d93a51,
+                0x82fd55f0cecf49dc93154f215e5421dd,
+            ),
+            (
+                0x00000000000000000000000000000001,
+                0xc92b12aba31e4236a2296560761415ec,
+            ),
+        ];
+
+        for (app_id, expected) in vectors {
+            let derived = ID.app_specific_systemd(Uuid::from_u128(app_id));
+            assert_eq!(derived, MachineId(Uuid::from_u128(expected)));
+        }
+    }
+}
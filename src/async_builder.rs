@@ -0,0 +1,334 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::{
+    builder::{active_override, all_sources_failed, check_source},
+    error::{Error, SourceError},
+    sources::{AsyncIdSource, Blocking, IdSource, Override},
+    MachineId, MachineIdBuilder, Resolved, Result, Validator,
+};
+
+/// The async counterpart of [`MachineIdBuilder`], available with the `tokio` or `async-std` feature.
+///
+/// Blocking sources, including all built-in ones, run on the runtime's blocking thread pool,
+/// so resolving never blocks the executor. Each source can be given a timeout, after which
+/// the next one is tried.
+///
+/// Resolving needs a running runtime: with only the `tokio` feature, every source fails
+/// when polled outside a tokio runtime. Timeouts inside a tokio runtime use its timer,
+/// so that runtime must be built with the time driver (`enable_time` or `enable_all`).
+///
+/// ```no_run
+/// # async fn example() -> yamid::Result<()> {
+/// use std::time::Duration;
+///
+/// let id = yamid::AsyncMachineIdBuilder::new()
+///     .timeout(Duration::from_secs(1))
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncMachineIdBuilder {
+    sources: Vec<(Box<dyn AsyncIdSource>, Option<Duration>)>,
+    override_source: Option<Override>,
+    root: PathBuf,
+    validator: Validator,
+    timeout: Option<Duration>,
+}
+
+impl Default for AsyncMachineIdBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs the sources, override, root and validator of a [`MachineIdBuilder`] asynchronously.
+impl From<MachineIdBuilder> for AsyncMachineIdBuilder {
+    fn from(builder: MachineIdBuilder) -> Self {
+        let (sources, override_source, root, validator) = builder.into_parts();
+        Self {
+            sources: sources
+                .into_iter()
+                .map(|source| (Box::new(Blocking::from(source)) as _, None))
+                .collect(),
+            override_source,
+            root,
+            validator,
+            timeout: None,
+        }
+    }
+}
+
+impl AsyncMachineIdBuilder {
+    /// Creates a builder with the sources of [`MachineIdBuilder::new`].
+    pub fn new() -> Self {
+        MachineIdBuilder::new().into()
+    }
+
+    /// Creates a builder without any sources, see [`MachineIdBuilder::empty`].
+    pub fn empty() -> Self {
+        MachineIdBuilder::empty().into()
+    }
+
+    /// See [`MachineIdBuilder::root`].
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    /// See [`MachineIdBuilder::validator`].
+    pub fn validator(mut self, validator: Validator) -> Self {
+        self.validator = validator;
+        self
+    }
+
+    /// See [`MachineIdBuilder::allow_override`].
+    pub fn allow_override(mut self, allow: bool) -> Self {
+        self.override_source = allow.then(Override::new);
+        self
+    }
+
+    /// Sets how long each source without its own timeout may take.
+    ///
+    /// A source that times out fails with an [`std::io::ErrorKind::TimedOut`] error.
+    ///
+    /// Within a tokio runtime, the runtime must have the time driver enabled,
+    /// otherwise tokio panics when the timeout starts.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Adds a source to the end of the list.
+    pub fn source(mut self, source: impl AsyncIdSource + 'static) -> Self {
+        self.sources.push((Box::new(source), None));
+        self
+    }
+
+    /// Adds a source to the end of the list, with a timeout overriding [`AsyncMachineIdBuilder::timeout`].
+    ///
+    /// Needs the tokio time driver like [`AsyncMachineIdBuilder::timeout`].
+    pub fn source_with_timeout(
+        mut self,
+        source: impl AsyncIdSource + 'static,
+        timeout: Duration,
+    ) -> Self {
+        self.sources.push((Box::new(source), Some(timeout)));
+        self
+    }
+
+    /// Adds a blocking source to the end of the list, see [`Blocking`].
+    pub fn blocking_source(self, source: impl IdSource + 'static) -> Self {
+        self.source(Blocking::new(source))
+    }
+
+    /// Removes all sources with the given name.
+    pub fn without(mut self, name: &str) -> Self {
+        self.sources.retain(|(source, _)| source.name() != name);
+        self
+    }
+
+    /// Names of the configured sources, in the order they are tried.
+    pub fn source_names(&self) -> impl Iterator<Item = &str> {
+        self.sources.iter().map(|(source, _)| source.name())
+    }
+
+    pub async fn build(&self) -> Result<MachineId> {
+        self.resolve().await.map(|resolved| resolved.id)
+    }
+
+    /// Tries all sources in order and reports which one provided the ID,
+    /// see [`MachineIdBuilder::resolve`].
+    pub async fn resolve(&self) -> Result<Resolved> {
+        // Like the blocking builder, the override is looked up on every call.
+        if let Some(source) = active_override(self.override_source.as_ref()) {
+            return self
+                .try_source(&Blocking::new(source.clone()), self.timeout)
+                .await
+                .map_err(|error| Error::AllSourcesFailed(vec![error]));
+        }
+
+        let mut errors = Vec::new();
+        for (source, timeout) in &self.sources {
+            match self.try_source(source, timeout.or(self.timeout)).await {
+                Ok(resolved) => return Ok(resolved),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        Err(all_sources_failed(errors))
+    }
+
+    async fn try_source(
+        &self,
+        source: &dyn AsyncIdSource,
+        timeout: Option<Duration>,
+    ) -> std::result::Result<Resolved, SourceError> {
+        let read = match timeout {
+            Some(timeout) => {
+                match crate::runtime::timeout(timeout, source.read(&self.root)).await {
+                    Ok(Some(read)) => read,
+                    Ok(None) => Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        format!("no ID within {timeout:?}"),
+                    )
+                    .into()),
+                    Err(error) => Err(error.into()),
+                }
+            }
+            None => source.read(&self.root).await,
+        };
+        check_source(
+            source.name(),
+            || source.location(&self.root),
+            read,
+            &self.validator,
+        )
+    }
+}
+
+impl MachineId {
+    /// Reads the machine ID like [`MachineId::new`] without blocking the async runtime,
+    /// see [`AsyncMachineIdBuilder`].
+    ///
+    /// With only the `tokio` feature it must be awaited within a tokio runtime,
+    /// elsewhere it fails with an [`std::io::ErrorKind::Unsupported`] error.
+    pub async fn new_async() -> Result<Self> {
+        AsyncMachineIdBuilder::new().build().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        sources::{self, BoxFuture},
+        test_util::TempRoot,
+    };
+
+    struct Hanging;
+
+    impl AsyncIdSource for Hanging {
+        fn name(&self) -> &str {
+            "hanging"
+        }
+
+        fn read<'a>(&'a self, _root: &'a Path) -> BoxFuture<'a, Result<Uuid>> {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    async fn check_timeouts() {
+        let root = TempRoot::new();
+        root.write("etc/machine-id", "3d1219c7c4c5404aaa1f6d2a48adfda4\n");
+
+        let builder = AsyncMachineIdBuilder::empty()
+            .source_with_timeout(Hanging, Duration::from_millis(10))
+            .blocking_source(sources::MachineIdFile)
+            .root(root.path());
+        assert_eq!(
+            builder.source_names().collect::<Vec<_>>(),
+            ["hanging", "machine-id"]
+        );
+
+        let resolved = builder.resolve().await.unwrap();
+        assert_eq!(resolved.source, "machine-id");
+        assert_eq!(
+            resolved.id,
+            MachineId(Uuid::from_u128(0x3d1219c7c4c5404aaa1f6d2a48adfda4))
+        );
+
+        let error = AsyncMachineIdBuilder::empty()
+            .source(Hanging)
+            .timeout(Duration::from_millis(10))
+            .resolve()
+            .await
+            .unwrap_err();
+        let Error::IoError(error) = error.source_errors()[0].error.as_ref() else {
+            panic!("expected a timeout, got {error}");
+        };
+        assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+    }
+
+    async fn check_override(var: &str) {
+        let os = sources::from_fn("os", || {
+            Ok(Uuid::from_u128(0x3d1219c7c4c5404aaa1f6d2a48adfda4))
+        });
+        let builder: AsyncMachineIdBuilder = MachineIdBuilder::empty()
+            .source(os)
+            .override_source(Override::from_vars(var, format!("{var}_FILE")))
+            .into();
+        assert_eq!(builder.resolve().await.unwrap().source, "os");
+
+        // Set after the builder was created.
+        std::env::set_var(var, "0f1e2d3c4b5a69788796a5b4c3d2e1f0");
+        let resolved = builder.resolve().await.unwrap();
+        assert_eq!(resolved.source, "override");
+        assert_eq!(
+            resolved.id,
+            MachineId(Uuid::from_u128(0x0f1e2d3c4b5a69788796a5b4c3d2e1f0))
+        );
+
+        let builder = builder.allow_override(false);
+        assert_eq!(builder.resolve().await.unwrap().source, "os");
+
+        std::env::remove_var(var);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_tokio() {
+        assert_eq!(
+            MachineId::new_async().await.unwrap(),
+            MachineId::new().unwrap()
+        );
+        check_timeouts().await;
+        check_override("YAMID_TEST_TOKIO_OVERRIDE").await;
+    }
+
+    /// Outside a tokio runtime, with no other runtime to fall back to.
+    #[cfg(all(feature = "tokio", not(feature = "async-std")))]
+    #[test]
+    fn test_tokio_without_runtime() {
+        use std::{
+            future::Future,
+            sync::Arc,
+            task::{Context, Poll, Wake, Waker},
+        };
+
+        struct Noop;
+
+        impl Wake for Noop {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        // The future fails before awaiting anything, so polling once is enough.
+        let mut future = std::pin::pin!(MachineId::new_async());
+        let waker = Waker::from(Arc::new(Noop));
+        let Poll::Ready(result) = future.as_mut().poll(&mut Context::from_waker(&waker)) else {
+            panic!("expected an immediate error");
+        };
+        let error = result.unwrap_err();
+        let Error::IoError(error) = error.source_errors()[0].error.as_ref() else {
+            panic!("expected an I/O error, got {error}");
+        };
+        assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+    }
+
+    #[cfg(feature = "async-std")]
+    #[test]
+    fn test_async_std() {
+        async_std::task::block_on(async {
+            assert_eq!(
+                MachineId::new_async().await.unwrap(),
+                MachineId::new().unwrap()
+            );
+            check_timeouts().await;
+            check_override("YAMID_TEST_ASYNC_STD_OVERRIDE").await;
+        });
+    }
+}
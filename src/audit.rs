@@ -0,0 +1,376 @@
+//! Consistency checks between the copies of the Linux machine ID.
+//!
+//! `MachineId::new` prefers `/etc/machine-id` and never looks at the other copies,
+//! so a stale `/var/lib/dbus/machine-id` goes unnoticed until D-Bus or deduplication breaks.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    dmi::Dmi,
+    error::{Error, Location},
+    sources::{parse_id, read_id_file, resolve_path},
+    MachineId, Validator,
+};
+
+const CMDLINE_PARAM: &str = "systemd.machine_id=";
+
+/// A place the machine ID is kept, in the order systemd gives them precedence.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Place {
+    /// `systemd.machine_id=` on the kernel command line, which systemd applies at boot.
+    KernelCmdline,
+    /// `/etc/machine-id`
+    Etc,
+    /// `/run/machine-id`, the transient ID of the current boot.
+    Run,
+    /// `/var/lib/dbus/machine-id`
+    Dbus,
+}
+
+impl Place {
+    pub const ALL: [Place; 4] = [Place::KernelCmdline, Place::Etc, Place::Run, Place::Dbus];
+
+    fn path(self) -> &'static str {
+        match self {
+            Place::KernelCmdline => "/proc/cmdline",
+            Place::Etc => "/etc/machine-id",
+            Place::Run => "/run/machine-id",
+            Place::Dbus => "/var/lib/dbus/machine-id",
+        }
+    }
+}
+
+impl std::fmt::Display for Place {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Place::KernelCmdline => "kernel-cmdline",
+            Place::Etc => "etc",
+            Place::Run => "run",
+            Place::Dbus => "dbus",
+        })
+    }
+}
+
+/// What a [`Place`] holds.
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Status {
+    Missing,
+    /// Empty or systemd's `uninitialized` first boot marker.
+    Uninitialized,
+    /// Unreadable, unparsable or rejected by the [`Validator`].
+    Invalid(String),
+    Valid(MachineId),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Entry {
+    pub place: Place,
+    pub location: Location,
+    pub status: Status,
+}
+
+/// The result of [`audit`].
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Report {
+    /// Every [`Place`], in order of precedence.
+    pub entries: Vec<Entry>,
+}
+
+impl Report {
+    /// The ID systemd uses: the first valid one in order of precedence.
+    pub fn expected(&self) -> Option<MachineId> {
+        self.entries.iter().find_map(|entry| match entry.status {
+            Status::Valid(id) => Some(id),
+            _ => None,
+        })
+    }
+
+    /// Copies that hold a different or an invalid ID.
+    ///
+    /// Missing and uninitialized copies are not mismatches, systemd creates them as needed.
+    pub fn mismatches(&self) -> impl Iterator<Item = &Entry> {
+        let expected = self.expected();
+        self.entries
+            .iter()
+            .filter(move |entry| match &entry.status {
+                Status::Valid(id) => Some(*id) != expected,
+                Status::Invalid(_) => true,
+                Status::Missing | Status::Uninitialized => false,
+            })
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches().next().is_none()
+    }
+}
+
+/// Lists the copies without their values, which are confidential.
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let expected = self.expected();
+        for entry in &self.entries {
+            let status = match &entry.status {
+                Status::Missing => "missing".to_owned(),
+                Status::Uninitialized => "uninitialized".to_owned(),
+                Status::Invalid(error) => format!("invalid: {error}"),
+                Status::Valid(id) if Some(*id) == expected => "ok".to_owned(),
+                Status::Valid(_) => "mismatch".to_owned(),
+            };
+            writeln!(f, "{} ({}): {status}", entry.place, entry.location)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares every copy of the machine ID on the running system, see [`audit_root`].
+pub fn audit() -> Report {
+    audit_root("/")
+}
+
+/// Compares every copy of the machine ID of the system mounted at `root`.
+pub fn audit_root(root: impl AsRef<Path>) -> Report {
+    let root = root.as_ref();
+    let entries = Place::ALL
+        .into_iter()
+        .map(|place| Entry {
+            place,
+            location: Location::File(resolve_path(root, place.path().as_ref())),
+            status: match place {
+                Place::KernelCmdline => cmdline_status(root),
+                _ => file_status(&copy_path(root, place)),
+            },
+        })
+        .collect();
+    Report { entries }
+}
+
+/// Rewrites stale copies under `root` with the first valid ID in a file, and returns the new report.
+///
+/// `systemd.machine_id=` on the kernel command line is never persisted: it only applies
+/// to the current boot, so the returned report still lists it if it differs.
+///
+/// `/etc/machine-id` is always written if it does not hold the ID, `/run` and
+/// D-Bus copies only if they exist. A D-Bus copy that is a symlink is left alone,
+/// and a copy that is a mount point, like systemd's transient bind mount over
+/// `/etc/machine-id`, is an error: it is never unmounted.
+#[cfg(unix)]
+pub fn repair(root: impl AsRef<Path>) -> crate::Result<Report> {
+    let root = root.as_ref();
+    let report = audit_root(root);
+    let id = report
+        .entries
+        .iter()
+        .filter(|entry| entry.place != Place::KernelCmdline)
+        .find_map(|entry| match entry.status {
+            Status::Valid(id) => Some(id),
+            _ => None,
+        });
+    let id = id.ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "no valid machine ID to repair from",
+        )
+    })?;
+
+    for entry in &report.entries {
+        let stale = entry.status != Status::Valid(id);
+        let write = match entry.place {
+            Place::KernelCmdline => false,
+            Place::Etc => stale,
+            Place::Run => stale && entry.status != Status::Missing,
+            Place::Dbus => {
+                let path = resolve_path(root, entry.place.path().as_ref());
+                let symlink = std::fs::symlink_metadata(path)
+                    .is_ok_and(|metadata| metadata.file_type().is_symlink());
+                stale && entry.status != Status::Missing && !symlink
+            }
+        };
+        if write {
+            crate::provision::write(root, entry.place.path(), &id)?;
+        }
+    }
+
+    crate::cache::invalidate();
+    Ok(audit_root(root))
+}
+
+/// The file to read for `place`, following an absolute symlink (like the usual
+/// `/var/lib/dbus/machine-id -> /etc/machine-id`) within `root`.
+fn copy_path(root: &Path, place: Place) -> PathBuf {
+    let path = resolve_path(root, place.path().as_ref());
+    match std::fs::read_link(&path) {
+        Ok(target) if target.is_absolute() => resolve_path(root, &target),
+        _ => path,
+    }
+}
+
+fn file_status(path: &Path) -> Status {
+    match read_id_file(path) {
+        Ok(id) => validate(id),
+        Err(Error::IoError(error)) if error.kind() == std::io::ErrorKind::NotFound => {
+            Status::Missing
+        }
+        Err(Error::Uninitialized { .. }) => Status::Uninitialized,
+        Err(error) => Status::Invalid(error.to_string()),
+    }
+}
+
+fn cmdline_status(root: &Path) -> Status {
+    let Ok(cmdline) =
+        std::fs::read_to_string(resolve_path(root, Place::KernelCmdline.path().as_ref()))
+    else {
+        return Status::Missing;
+    };
+    // The last occurrence wins, like for other kernel parameters.
+    let Some(value) = cmdline
+        .split_whitespace()
+        .filter_map(|param| param.strip_prefix(CMDLINE_PARAM))
+        .next_back()
+    else {
+        return Status::Missing;
+    };
+
+    // `firmware` asks systemd to use the SMBIOS system UUID.
+    if value == "firmware" {
+        return match Dmi::from_root(root).ok().and_then(|dmi| dmi.product_uuid) {
+            Some(id) => validate(id),
+            None => Status::Invalid("no firmware UUID".to_owned()),
+        };
+    }
+
+    match parse_id(value) {
+        Ok(id) => validate(id),
+        Err(error) => Status::Invalid(error.to_string()),
+    }
+}
+
+fn validate(id: uuid::Uuid) -> Status {
+    match Validator::new().validate(&id) {
+        Ok(()) => Status::Valid(MachineId(id)),
+        Err(error) => Status::Invalid(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::test_util::TempRoot;
+
+    const ETC: &str = "3d1219c7c4c5404aaa1f6d2a48adfda4";
+    const OTHER: &str = "0f1e2d3c4b5a69788796a5b4c3d2e1f0";
+
+    fn id(value: &str) -> MachineId {
+        MachineId(Uuid::parse_str(value).unwrap())
+    }
+
+    fn status(report: &Report, place: Place) -> &Status {
+        &report.entries[place as usize].status
+    }
+
+    #[test]
+    fn test_consistent() {
+        let root = TempRoot::new();
+        root.write("etc/machine-id", format!("{ETC}\n"));
+        root.write("run/machine-id", format!("{ETC}\n"));
+        root.write("proc/cmdline", "BOOT_IMAGE=/vmlinuz root=/dev/vda1 quiet\n");
+        #[cfg(unix)]
+        {
+            std::fs::create_dir_all(root.path().join("var/lib/dbus")).unwrap();
+            std::os::unix::fs::symlink(
+                "/etc/machine-id",
+                root.path().join("var/lib/dbus/machine-id"),
+            )
+            .unwrap();
+        }
+
+        let report = audit_root(root.path());
+        assert!(report.is_consistent(), "{report}");
+        assert_eq!(report.expected(), Some(id(ETC)));
+        assert_eq!(status(&report, Place::KernelCmdline), &Status::Missing);
+    }
+
+    #[test]
+    fn test_mismatches() {
+        let root = TempRoot::new();
+        root.write("etc/machine-id", format!("{ETC}\n"));
+        root.write("var/lib/dbus/machine-id", format!("{OTHER}\n"));
+        root.write("run/machine-id", "not an id\n");
+
+        let report = audit_root(root.path());
+        assert_eq!(report.expected(), Some(id(ETC)));
+        let mismatches: Vec<Place> = report.mismatches().map(|entry| entry.place).collect();
+        assert_eq!(mismatches, [Place::Run, Place::Dbus]);
+
+        let text = report.to_string();
+        assert!(text.contains("etc ("));
+        assert!(text.contains("machine-id): ok\n"));
+        assert!(text.contains("dbus/machine-id): mismatch\n"));
+        assert!(!text.contains(OTHER));
+    }
+
+    #[test]
+    fn test_kernel_cmdline_takes_precedence() {
+        let root = TempRoot::new();
+        root.write("etc/machine-id", format!("{ETC}\n"));
+        root.write(
+            "proc/cmdline",
+            format!("quiet systemd.machine_id=ffffffffffffffffffffffffffffffff systemd.machine_id={OTHER}\n"),
+        );
+
+        let report = audit_root(root.path());
+        assert_eq!(report.expected(), Some(id(OTHER)));
+        assert_eq!(
+            report
+                .mismatches()
+                .map(|entry| entry.place)
+                .collect::<Vec<_>>(),
+            [Place::Etc]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_repair() {
+        let root = TempRoot::new();
+        root.write("etc/machine-id", "uninitialized\n");
+        root.write("run/machine-id", format!("{ETC}\n"));
+        root.write("var/lib/dbus/machine-id", format!("{OTHER}\n"));
+
+        let report = repair(root.path()).unwrap();
+        assert!(report.is_consistent(), "{report}");
+        assert_eq!(status(&report, Place::Etc), &Status::Valid(id(ETC)));
+        assert_eq!(status(&report, Place::Dbus), &Status::Valid(id(ETC)));
+        assert_eq!(MachineId::from_root(root.path()).unwrap(), id(ETC));
+
+        let empty = TempRoot::new();
+        assert!(repair(empty.path()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_repair_ignores_kernel_cmdline() {
+        let root = TempRoot::new();
+        root.write("etc/machine-id", format!("{ETC}\n"));
+        root.write(
+            "proc/cmdline",
+            format!("quiet systemd.machine_id={OTHER}\n"),
+        );
+
+        let report = repair(root.path()).unwrap();
+        assert_eq!(status(&report, Place::Etc), &Status::Valid(id(ETC)));
+        assert_eq!(MachineId::from_root(root.path()).unwrap(), id(ETC));
+
+        let root = TempRoot::new();
+        root.write("proc/cmdline", format!("systemd.machine_id={OTHER}\n"));
+        assert!(repair(root.path()).is_err());
+        assert!(!root.path().join("etc/machine-id").exists());
+    }
+}
@@ -0,0 +1,346 @@
+//! Prints the machine ID exactly as the `yamid` crate computes it.
+
+use std::process::ExitCode;
+
+use yamid::{error::Error, MachineId, MachineIdBuilder};
+
+const USAGE: &str = "\
+Usage: yamid [OPTIONS]
+
+Prints the machine ID.
+
+Options:
+  -f, --format <FORMAT>        display (default, uppercase hyphenated), hyphenated, simple,
+                               braced, urn, base32, base64url or systemd
+  -a, --app-id <APP>           print the app-specific ID for APP instead, may be repeated
+      --systemd-app-id <UUID>  print the systemd-compatible app-specific ID instead, may be repeated
+  -j, --json                   print JSON
+  -d, --diagnose               report on every known source
+      --no-redact              do not mask values in the diagnostic report
+  -h, --help                   print this help
+  -V, --version                print the version
+
+Exit codes:
+  0  success
+  1  unexpected error
+  2  invalid arguments
+  3  no source provided an ID
+  4  the machine ID is not initialized yet
+  5  the ID is invalid
+  6  the ID is a known placeholder or duplicate
+  7  the platform is not supported
+  8  permission denied
+
+When several sources fail, the most significant reason wins, in this order:
+6, 5, 4, 8, 7, 3.
+";
+
+#[derive(Debug, PartialEq, Eq)]
+enum AppId {
+    Yamid(String),
+    Systemd(uuid::Uuid),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Args {
+    /// `None` for the `Display` form.
+    format: Option<yamid::Format>,
+    app_ids: Vec<AppId>,
+    json: bool,
+    diagnose: bool,
+    redact: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Run(Args),
+    Help,
+    Version,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut parsed = Args {
+        format: None,
+        app_ids: Vec::new(),
+        json: false,
+        diagnose: false,
+        redact: true,
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                (flag.to_owned(), Some(value.to_owned()))
+            }
+            _ => (arg, None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{flag} requires a value"))
+        };
+
+        match flag.as_str() {
+            "-f" | "--format" => {
+                parsed.format = match value()?.as_str() {
+                    "display" => None,
+                    other => Some(other.parse().map_err(|e| format!("{e}"))?),
+                }
+            }
+            "-a" | "--app-id" => parsed.app_ids.push(AppId::Yamid(value()?)),
+            "--systemd-app-id" => {
+                let app_id = value()?;
+                let uuid = uuid::Uuid::parse_str(&app_id)
+                    .map_err(|e| format!("invalid systemd app ID `{app_id}`: {e}"))?;
+                parsed.app_ids.push(AppId::Systemd(uuid));
+            }
+            "-j" | "--json" => parsed.json = true,
+            "-d" | "--diagnose" => parsed.diagnose = true,
+            "--no-redact" => parsed.redact = false,
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            other => return Err(format!("unexpected argument `{other}`")),
+        }
+    }
+
+    Ok(Command::Run(parsed))
+}
+
+fn format_id(id: &MachineId, format: Option<yamid::Format>) -> String {
+    match format {
+        Some(format) => id.format(format),
+        None => id.to_string(),
+    }
+}
+
+/// Exit codes of source failures, most significant first: a source that returned
+/// a suspicious or invalid ID says more than one that could not be read at all.
+const EXIT_CODE_PRIORITY: [u8; 6] = [6, 5, 4, 8, 7, 3];
+
+fn exit_code(error: &Error) -> u8 {
+    fn code(error: &Error) -> u8 {
+        match error {
+            Error::Uninitialized { .. } => 4,
+            Error::InvalidContent { .. } | Error::InvalidUuid(_) => 5,
+            Error::SuspiciousId { .. } => 6,
+            Error::Unsupported { .. } => 7,
+            Error::IoError(error) if error.kind() == std::io::ErrorKind::PermissionDenied => 8,
+            _ => 3,
+        }
+    }
+
+    match error {
+        Error::AllSourcesFailed(errors) => errors
+            .iter()
+            .map(|error| code(&error.error))
+            .min_by_key(|code| EXIT_CODE_PRIORITY.iter().position(|c| c == code))
+            .unwrap_or(3),
+        error => code(error),
+    }
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    if args.diagnose {
+        let report = yamid::diagnose_with(&yamid::DiagnoseOptions {
+            redact: args.redact,
+            ..Default::default()
+        });
+        if args.json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).expect("serializable")
+            );
+        } else {
+            print!("{report}");
+        }
+        return Ok(());
+    }
+
+    let resolved = MachineIdBuilder::new().resolve()?;
+
+    if args.app_ids.is_empty() {
+        let id = format_id(&resolved.id, args.format);
+        if args.json {
+            let json = serde_json::json!({ "id": id, "source": resolved.source });
+            println!("{json}");
+        } else {
+            println!("{id}");
+        }
+        return Ok(());
+    }
+
+    let derived: Vec<(String, String)> = args
+        .app_ids
+        .iter()
+        .map(|app_id| match app_id {
+            AppId::Yamid(app) => (app.clone(), resolved.id.app_specific(app)),
+            AppId::Systemd(app) => (
+                app.simple().to_string(),
+                resolved.id.app_specific_systemd(*app),
+            ),
+        })
+        .map(|(app, id)| (app, format_id(&id, args.format)))
+        .collect();
+
+    if args.json {
+        let app_specific: Vec<_> = derived
+            .iter()
+            .map(|(app, id)| serde_json::json!({ "app_id": app, "id": id }))
+            .collect();
+        let json = serde_json::json!({ "source": resolved.source, "app_specific": app_specific });
+        println!("{json}");
+    } else if let [(_, id)] = derived.as_slice() {
+        println!("{id}");
+    } else {
+        for (app, id) in derived {
+            println!("{app}\t{id}");
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Command::Run(args)) => args,
+        Ok(Command::Help) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Ok(Command::Version) => {
+            println!("yamid {}", env!("CARGO_PKG_VERSION"));
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("yamid: {message}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("yamid: {error}");
+            ExitCode::from(exit_code(&error))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let Ok(Command::Run(args)) = parse(&[
+            "--format=systemd",
+            "-a",
+            "com.example.app",
+            "--systemd-app-id",
+            "b08f2b8d3ad64b87a9c1e8a2c6d93a51",
+            "-j",
+        ]) else {
+            panic!("expected run command");
+        };
+        assert_eq!(args.format, Some(yamid::Format::Systemd));
+        assert_eq!(
+            args.app_ids,
+            [
+                AppId::Yamid("com.example.app".to_owned()),
+                AppId::Systemd(uuid::Uuid::from_u128(0xb08f2b8d3ad64b87a9c1e8a2c6d93a51)),
+            ]
+        );
+        assert!(args.json);
+
+        assert_eq!(parse(&["-h"]), Ok(Command::Help));
+        assert!(parse(&["--format"]).is_err());
+        assert!(parse(&["--format", "xml"]).is_err());
+        assert!(parse(&["--format", "hex"]).is_err());
+        let Ok(Command::Run(args)) = parse(&["-f", "display"]) else {
+            panic!("expected run command");
+        };
+        assert_eq!(args.format, None);
+        assert!(parse(&["--systemd-app-id", "app"]).is_err());
+        assert!(parse(&["extra"]).is_err());
+    }
+
+    #[test]
+    fn test_formats() {
+        let id: MachineId = "3d1219c7c4c5404aaa1f6d2a48adfda4".parse().unwrap();
+        assert_eq!(format_id(&id, None), "3D1219C7-C4C5-404A-AA1F-6D2A48ADFDA4");
+        // The same names as the library, with the same output.
+        for format in yamid::Format::ALL {
+            let Ok(Command::Run(args)) = parse(&["--format", format.name()]) else {
+                panic!("expected run command");
+            };
+            assert_eq!(format_id(&id, args.format), id.format(format));
+        }
+        assert_eq!(
+            format_id(&id, Some(yamid::Format::Hyphenated)),
+            "3d1219c7-c4c5-404a-aa1f-6d2a48adfda4"
+        );
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let uninitialized = Error::Uninitialized {
+            path: "/etc/machine-id".into(),
+        };
+        let missing = Error::IoError(std::io::ErrorKind::NotFound.into());
+        let error = Error::AllSourcesFailed(
+            [uninitialized, missing]
+                .into_iter()
+                .map(|error| yamid::error::SourceError {
+                    name: "test".to_owned(),
+                    location: None,
+                    error: Box::new(error),
+                })
+                .collect(),
+        );
+
+        assert_eq!(exit_code(&error), 4);
+        assert_eq!(exit_code(&Error::AllSourcesFailed(Vec::new())), 3);
+    }
+
+    #[test]
+    fn test_exit_code_priority() {
+        let failed = |errors: Vec<Error>| {
+            Error::AllSourcesFailed(
+                errors
+                    .into_iter()
+                    .map(|error| yamid::error::SourceError {
+                        name: "test".to_owned(),
+                        location: None,
+                        error: Box::new(error),
+                    })
+                    .collect(),
+            )
+        };
+        let suspicious = || Error::SuspiciousId {
+            value: uuid::Uuid::nil(),
+            reason: "nil UUID".to_owned(),
+        };
+        let denied = || Error::IoError(std::io::ErrorKind::PermissionDenied.into());
+        let missing = || Error::IoError(std::io::ErrorKind::NotFound.into());
+        let unsupported = || Error::Unsupported {
+            target: "wasm32-unknown".to_owned(),
+        };
+
+        assert_eq!(exit_code(&failed(vec![missing(), denied()])), 8);
+        assert_eq!(exit_code(&failed(vec![unsupported(), denied()])), 8);
+        assert_eq!(
+            exit_code(&failed(vec![denied(), missing(), suspicious()])),
+            6
+        );
+        assert_eq!(exit_code(&failed(vec![unsupported(), missing()])), 7);
+
+        let content = Error::InvalidUuid(uuid::Uuid::parse_str("xyz").unwrap_err());
+        assert_eq!(exit_code(&failed(vec![denied(), content])), 5);
+    }
+}
@@ -0,0 +1,119 @@
+use uuid::Uuid;
+
+use crate::{app_specific, Result};
+
+/// A random ID generated by the kernel on every boot.
+///
+/// Together with [`crate::MachineId`] it tells "same machine, new boot" apart from "new machine".
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BootId(Uuid);
+
+impl std::fmt::Display for BootId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::UpperHex::fmt(&self.0, f)
+    }
+}
+
+impl AsRef<Uuid> for BootId {
+    #[inline]
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for BootId {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+impl BootId {
+    /// Reads `/proc/sys/kernel/random/boot_id`.
+    #[cfg(target_os = "linux")]
+    pub fn new() -> Result<Self> {
+        let id = std::fs::read_to_string("/proc/sys/kernel/random/boot_id")?;
+        Ok(Self(Uuid::parse_str(id.trim_end())?))
+    }
+
+    /// Reads the `kern.bootsessionuuid` sysctl.
+    #[cfg(target_os = "macos")]
+    pub fn new() -> Result<Self> {
+        let mut buf = [0u8; 64];
+        let mut len = buf.len();
+        let r = unsafe {
+            libc::sysctlbyname(
+                "kern.bootsessionuuid\0".as_ptr() as _,
+                buf.as_mut_ptr() as _,
+                &mut len,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+
+        if r != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        let id = buf[..len]
+            .iter()
+            .take_while(|ch| **ch != 0)
+            .map(|ch| *ch as char)
+            .collect::<String>();
+        Ok(Self(Uuid::parse_str(&id)?))
+    }
+
+    /// Fails with [`crate::error::Error::Unsupported`], there is no boot ID on this platform.
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    pub fn new() -> Result<Self> {
+        Err(crate::error::Error::Unsupported {
+            target: crate::error::current_target(),
+        })
+    }
+
+    /// Derives an application-specific boot ID exactly like systemd's `sd_id128_get_boot_app_specific()`
+    /// and `systemd-id128 boot-id --app-specific=<app_id>` do.
+    pub fn app_specific_systemd(&self, app_id: Uuid) -> BootId {
+        BootId(app_specific::systemd_app_specific(&self.0, &app_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_get_boot_id() {
+        let first = BootId::new().unwrap();
+        let second = BootId::new().unwrap();
+
+        println!("Boot id: {first}");
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_app_specific_systemd() {
+        // Output of systemd 252's `systemd-id128 boot-id --app-specific=<app_id>`, with the
+        // fixture bind-mounted over `/proc/sys/kernel/random/boot_id` in a private mount
+        // namespace (`unshare -m`).
+        let id = BootId(Uuid::from_u128(0xffe5e08a6be34ba493ffcf0840541be3));
+        let derived = id.app_specific_systemd(Uuid::from_u128(0xb08f2b8d3ad64b87a9c1e8a2c6d93a51));
+        assert_eq!(
+            derived,
+            BootId(Uuid::from_u128(0xe0e177f17a8749f3b2170b06adc22560))
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let id = BootId(Uuid::from_u128(0xffe5e08a6be34ba493ffcf0840541be3));
+        let s = serde_json::to_string(&id).unwrap();
+
+        let de: BootId = serde_json::from_str(&s).unwrap();
+        assert_eq!(id, de);
+    }
+}
@@ -0,0 +1,468 @@
+use std::path::PathBuf;
+
+use uuid::Uuid;
+
+use crate::{
+    error::{Error, Location, SourceError},
+    sources::{IdSource, Override},
+    MachineId, Result, Validator,
+};
+
+/// A machine ID together with the name of the source that provided it.
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Resolved {
+    pub id: MachineId,
+    pub source: String,
+}
+
+/// Tries an ordered list of [`IdSource`]s and returns the first ID found.
+///
+/// ```no_run
+/// use yamid::{sources, MachineIdBuilder};
+///
+/// let resolved = MachineIdBuilder::new()
+///     .prepend(sources::Env::new("MY_APP_MACHINE_ID"))
+///     .without("dbus")
+///     .resolve()?;
+///
+/// println!("{} from {}", resolved.id, resolved.source);
+/// # Ok::<(), yamid::error::Error>(())
+/// ```
+pub struct MachineIdBuilder {
+    sources: Vec<Box<dyn IdSource>>,
+    root: PathBuf,
+    validator: Validator,
+    override_source: Option<Override>,
+}
+
+impl Default for MachineIdBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MachineIdBuilder {
+    /// Creates a builder with the platform default sources, the same ones `MachineId::new` uses,
+    /// see [`crate::sources::default_sources`].
+    ///
+    /// The [`Override`] is allowed.
+    pub fn new() -> Self {
+        Self {
+            sources: crate::sources::default_sources(),
+            root: PathBuf::from("/"),
+            validator: Validator::new(),
+            override_source: Some(Override::new()),
+        }
+    }
+
+    /// Creates a builder without any sources.
+    ///
+    /// The [`Override`] is not allowed.
+    pub fn empty() -> Self {
+        Self {
+            sources: Vec::new(),
+            root: PathBuf::from("/"),
+            validator: Validator::new(),
+            override_source: None,
+        }
+    }
+
+    /// Resolves file-based sources against `root` instead of `/`,
+    /// e.g. to read the ID of a mounted image, a chroot or a container rootfs.
+    ///
+    /// Sources that query the running system (registry, IOKit, sysctl) fail for any root other than `/`.
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    /// Replaces the [`Validator`] every ID is checked with.
+    ///
+    /// A source that returns a suspicious ID is treated as failed, and the next one is tried.
+    pub fn validator(mut self, validator: Validator) -> Self {
+        self.validator = validator;
+        self
+    }
+
+    /// Allows or refuses the [`Override`].
+    ///
+    /// Has no effect in builds with `--cfg yamid_no_override`, which always refuse it.
+    pub fn allow_override(mut self, allow: bool) -> Self {
+        self.override_source = allow.then(Override::new);
+        self
+    }
+
+    /// Allows an [`Override`] read from custom environment variables.
+    pub fn override_source(mut self, source: Override) -> Self {
+        self.override_source = Some(source);
+        self
+    }
+
+    /// Adds a source to the end of the list.
+    pub fn source(mut self, source: impl IdSource + 'static) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// Adds a source to the beginning of the list.
+    pub fn prepend(mut self, source: impl IdSource + 'static) -> Self {
+        self.sources.insert(0, Box::new(source));
+        self
+    }
+
+    /// Removes all sources with the given name.
+    pub fn without(mut self, name: &str) -> Self {
+        self.sources.retain(|source| source.name() != name);
+        self
+    }
+
+    /// Names of the configured sources, in the order they are tried.
+    pub fn source_names(&self) -> impl Iterator<Item = &str> {
+        self.sources.iter().map(|source| source.name())
+    }
+
+    /// Files the sources read, the active override included.
+    pub(crate) fn files(&self) -> Vec<PathBuf> {
+        let override_source = self.active_override().map(|source| source as &dyn IdSource);
+        override_source
+            .into_iter()
+            .chain(self.sources.iter().map(|source| source.as_ref()))
+            .filter_map(|source| match source.location(&self.root) {
+                Some(Location::File(path)) => Some(path),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn build(&self) -> Result<MachineId> {
+        self.resolve().map(|resolved| resolved.id)
+    }
+
+    /// Tries all sources in order and reports which one provided the ID.
+    ///
+    /// If every source fails or returns a suspicious ID, [`Error::AllSourcesFailed`]
+    /// lists what went wrong with each of them. On targets without built-in sources,
+    /// [`Error::Unsupported`] is returned unless a source was added or registered.
+    ///
+    /// If an [`Override`] is allowed and set, it is the only source consulted: an invalid
+    /// override is an error rather than a reason to silently use the OS-provided ID.
+    pub fn resolve(&self) -> Result<Resolved> {
+        if let Some(source) = self.active_override() {
+            return self
+                .try_source(source)
+                .map_err(|error| Error::AllSourcesFailed(vec![error]));
+        }
+
+        let mut errors = Vec::new();
+        for source in &self.sources {
+            match self.try_source(source.as_ref()) {
+                Ok(resolved) => return Ok(resolved),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        Err(all_sources_failed(errors))
+    }
+
+    /// The sources, allowed override, root and validator [`MachineIdBuilder::resolve`] would use.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub(crate) fn into_parts(
+        self,
+    ) -> (Vec<Box<dyn IdSource>>, Option<Override>, PathBuf, Validator) {
+        (
+            self.sources,
+            self.override_source,
+            self.root,
+            self.validator,
+        )
+    }
+
+    fn try_source(&self, source: &dyn IdSource) -> std::result::Result<Resolved, SourceError> {
+        let read = source.read(&self.root);
+        check_source(
+            source.name(),
+            || source.location(&self.root),
+            read,
+            &self.validator,
+        )
+    }
+
+    fn active_override(&self) -> Option<&Override> {
+        active_override(self.override_source.as_ref())
+    }
+}
+
+/// The allowed override, if it is set and the build does not refuse overrides.
+pub(crate) fn active_override(source: Option<&Override>) -> Option<&Override> {
+    if cfg!(yamid_no_override) {
+        return None;
+    }
+    source.filter(|source| source.is_set())
+}
+
+/// Validates what a source read, attributing failures to it.
+pub(crate) fn check_source(
+    name: &str,
+    location: impl FnOnce() -> Option<Location>,
+    read: Result<Uuid>,
+    validator: &Validator,
+) -> std::result::Result<Resolved, SourceError> {
+    read.and_then(|uuid| validator.validate(&uuid).map(|_| uuid))
+        .map(|uuid| Resolved {
+            id: MachineId(uuid),
+            source: name.to_owned(),
+        })
+        .map_err(|error| SourceError {
+            name: name.to_owned(),
+            location: location(),
+            error: Box::new(error),
+        })
+}
+
+/// The error for a resolution in which every source failed.
+pub(crate) fn all_sources_failed(errors: Vec<SourceError>) -> Error {
+    if errors.is_empty() && !crate::sources::PLATFORM_SUPPORTED {
+        return Error::Unsupported {
+            target: crate::error::current_target(),
+        };
+    }
+    Error::AllSourcesFailed(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sources, test_util::TempRoot};
+
+    fn fixed(name: &'static str, value: u128) -> impl IdSource {
+        sources::from_fn(name, move || Ok(Uuid::from_u128(value)))
+    }
+
+    fn failing(name: &'static str) -> impl IdSource {
+        sources::from_fn(name, || {
+            Err(std::io::Error::from(std::io::ErrorKind::NotFound).into())
+        })
+    }
+
+    #[test]
+    fn test_first_successful_source_wins() {
+        let builder = MachineIdBuilder::empty()
+            .source(failing("first"))
+            .source(fixed("second", 2))
+            .source(fixed("third", 3));
+
+        let resolved = builder.resolve().unwrap();
+        assert_eq!(resolved.source, "second");
+        assert_eq!(resolved.id, MachineId(Uuid::from_u128(2)));
+
+        let resolved = builder.without("second").resolve().unwrap();
+        assert_eq!(resolved.source, "third");
+    }
+
+    #[test]
+    fn test_suspicious_ids_are_skipped() {
+        let builder = MachineIdBuilder::empty()
+            .source(fixed("nil", 0))
+            .source(fixed("golden", 0x3d1219c7c4c5404aaa1f6d2a48adfda4))
+            .source(fixed("valid", 0x0f1e2d3c4b5a69788796a5b4c3d2e1f0));
+
+        assert_eq!(builder.resolve().unwrap().source, "golden");
+
+        let golden = Uuid::from_u128(0x3d1219c7c4c5404aaa1f6d2a48adfda4);
+        let builder = builder.validator(Validator::new().deny(golden, "golden image"));
+        assert_eq!(builder.resolve().unwrap().source, "valid");
+
+        let error = builder.without("valid").resolve().unwrap_err();
+        let errors = error.source_errors();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1].name, "golden");
+        assert!(matches!(
+            *errors[1].error,
+            Error::SuspiciousId { ref reason, .. } if reason == "golden image"
+        ));
+    }
+
+    #[test]
+    fn test_no_sources() {
+        assert!(MachineIdBuilder::empty().build().is_err());
+        assert!(MachineIdBuilder::empty()
+            .source(failing("only"))
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_root() {
+        let root = TempRoot::new();
+        root.write("etc/machine-id", "");
+        root.write(
+            "var/lib/dbus/machine-id",
+            "0f1e2d3c4b5a69788796a5b4c3d2e1f0\n",
+        );
+
+        let resolved = MachineIdBuilder::empty()
+            .source(sources::MachineIdFile)
+            .source(sources::DbusMachineIdFile)
+            .root(root.path())
+            .resolve()
+            .unwrap();
+        assert_eq!(resolved.source, "dbus");
+        assert_eq!(
+            resolved.id,
+            MachineId(Uuid::from_u128(0x0f1e2d3c4b5a69788796a5b4c3d2e1f0))
+        );
+
+        root.write("etc/machine-id", "fedcba9876543210fedcba9876543210\n");
+        let id = MachineId::from_root(root.path()).unwrap();
+        assert_eq!(
+            id,
+            MachineId(Uuid::from_u128(0xfedcba9876543210fedcba9876543210))
+        );
+
+        let custom = sources::File::new("custom", "/opt/app/id");
+        root.write("opt/app/id", "fedcba9876543210fedcba9876543210");
+        let id = MachineIdBuilder::empty()
+            .source(custom)
+            .root(root.path())
+            .build()
+            .unwrap();
+        assert_eq!(
+            id,
+            MachineId(Uuid::from_u128(0xfedcba9876543210fedcba9876543210))
+        );
+    }
+
+    #[test]
+    fn test_uninitialized() {
+        let root = TempRoot::new();
+        root.write("etc/machine-id", "uninitialized\n");
+
+        let error = MachineId::from_root(root.path()).unwrap_err();
+        let errors = error.source_errors();
+        assert_eq!(errors.len(), 3);
+        assert!(
+            matches!(&*errors[0].error, Error::Uninitialized { path } if path.ends_with("etc/machine-id")),
+            "{error:?}"
+        );
+        assert_eq!(errors[2].name, "dbus");
+        assert_eq!(
+            errors[2].location,
+            Some(Location::File(root.path().join("var/lib/dbus/machine-id")))
+        );
+        assert!(errors[2].os_error().is_some());
+
+        root.write("run/machine-id", "0f1e2d3c4b5a69788796a5b4c3d2e1f0\n");
+        let resolved = MachineIdBuilder::empty()
+            .source(sources::MachineIdFile)
+            .source(sources::RunMachineIdFile)
+            .root(root.path())
+            .resolve()
+            .unwrap();
+        assert_eq!(resolved.source, "run");
+
+        root.write("etc/machine-id", "");
+        root.write("run/machine-id", "");
+        let error = MachineId::from_root(root.path()).unwrap_err();
+        assert!(matches!(
+            *error.source_errors()[1].error,
+            Error::Uninitialized { .. }
+        ));
+    }
+
+    #[test]
+    fn test_container_id() {
+        let root = TempRoot::new();
+        root.write(
+            "proc/self/cgroup",
+            "0::/system.slice/docker-4f2a6b1c9d8e7f60a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718.scope\n",
+        );
+
+        let resolved = MachineIdBuilder::empty()
+            .source(sources::ContainerId)
+            .root(root.path())
+            .resolve()
+            .unwrap();
+        assert_eq!(resolved.source, "container");
+        assert_eq!(
+            resolved.id,
+            MachineId(Uuid::from_u128(0x4f2a6b1c9d8e7f60a1b2c3d4e5f60718))
+        );
+    }
+
+    #[test]
+    fn test_override() {
+        let root = TempRoot::new();
+        let file = root.write("override", "0f1e2d3c4b5a69788796a5b4c3d2e1f0\n");
+        let source =
+            sources::Override::from_vars("YAMID_TEST_OVERRIDE", "YAMID_TEST_OVERRIDE_FILE");
+        let builder = MachineIdBuilder::empty().source(fixed("os", 2));
+
+        // Not set
+        let builder = builder.override_source(source);
+        assert_eq!(builder.resolve().unwrap().source, "os");
+
+        std::env::set_var("YAMID_TEST_OVERRIDE_FILE", &file);
+        let resolved = builder.resolve().unwrap();
+        assert_eq!(resolved.source, "override");
+        assert_eq!(
+            resolved.id,
+            MachineId(Uuid::from_u128(0x0f1e2d3c4b5a69788796a5b4c3d2e1f0))
+        );
+
+        std::env::set_var(
+            "YAMID_TEST_OVERRIDE",
+            "00000000-0000-0000-0000-000000000000",
+        );
+        let error = builder.resolve().unwrap_err();
+        assert_eq!(error.source_errors().len(), 1);
+        assert!(matches!(
+            *error.source_errors()[0].error,
+            Error::SuspiciousId { .. }
+        ));
+
+        std::env::set_var("YAMID_TEST_OVERRIDE", "not a uuid");
+        let error = builder.resolve().unwrap_err();
+        let error = &error.source_errors()[0];
+        assert_eq!(
+            error.location,
+            Some(Location::EnvVar("YAMID_TEST_OVERRIDE".to_owned()))
+        );
+        assert_eq!(error.raw().map(|raw| raw.reveal()), Some("not a uuid"));
+
+        let builder = builder.allow_override(false);
+        assert_eq!(builder.resolve().unwrap().source, "os");
+
+        std::env::remove_var("YAMID_TEST_OVERRIDE");
+        std::env::remove_var("YAMID_TEST_OVERRIDE_FILE");
+    }
+
+    #[test]
+    fn test_registered_sources() {
+        // A local registry, so the source does not leak into other tests.
+        let registry = sources::Registry::default();
+        sources::register_in(
+            &registry,
+            fixed("registered", 0x0f1e2d3c4b5a69788796a5b4c3d2e1f0),
+        );
+
+        let builder = MachineIdBuilder {
+            sources: sources::default_sources_with(&registry),
+            ..MachineIdBuilder::new()
+        };
+        assert_eq!(builder.source_names().last(), Some("registered"));
+        assert!(MachineIdBuilder::new()
+            .source_names()
+            .all(|name| name != "registered"));
+        assert!(MachineIdBuilder::empty().source_names().next().is_none());
+    }
+
+    #[test]
+    fn test_default_sources() {
+        let resolved = MachineIdBuilder::new().resolve().unwrap();
+        assert_eq!(resolved.id, MachineId::new().unwrap());
+        assert!(MachineIdBuilder::new()
+            .source_names()
+            .any(|name| name == resolved.source));
+    }
+}
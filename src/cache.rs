@@ -0,0 +1,138 @@
+//! The process-wide cache behind [`MachineId::cached`].
+
+use std::{
+    sync::{PoisonError, RwLock},
+    time::{Duration, Instant},
+};
+
+use crate::{MachineId, Result};
+
+static CACHE: Cache = Cache::new();
+
+/// Drops the cached ID, so the next [`MachineId::cached`] call reads it again.
+///
+/// Call it after changing the machine ID, e.g. once `systemd-firstboot` committed it.
+pub fn invalidate() {
+    CACHE.invalidate();
+}
+
+/// Sets how long [`MachineId::cached`] keeps the ID, `None` (the default) for the lifetime
+/// of the process. Also drops the cached ID.
+pub fn set_ttl(ttl: Option<Duration>) {
+    CACHE.set_ttl(ttl);
+}
+
+impl MachineId {
+    /// Returns the ID [`MachineId::new`] read on the first call, without touching
+    /// the filesystem or OS APIs again, see [`invalidate`] and [`set_ttl`].
+    ///
+    /// Errors are not cached: after a failure the next call tries again.
+    pub fn cached() -> Result<Self> {
+        CACHE.get_or_resolve(MachineId::new)
+    }
+}
+
+struct Entry {
+    id: MachineId,
+    /// Only taken with a TTL, as `Instant` is not available on every target.
+    expires: Option<Instant>,
+}
+
+struct Cache {
+    entry: RwLock<Option<Entry>>,
+    ttl: RwLock<Option<Duration>>,
+}
+
+impl Cache {
+    const fn new() -> Self {
+        Self {
+            entry: RwLock::new(None),
+            ttl: RwLock::new(None),
+        }
+    }
+
+    fn get_or_resolve(&self, resolve: impl FnOnce() -> Result<MachineId>) -> Result<MachineId> {
+        if let Some(id) = self.fresh(&self.entry.read().unwrap_or_else(PoisonError::into_inner)) {
+            return Ok(id);
+        }
+
+        // Holding the write lock while resolving makes concurrent callers wait
+        // for a single read instead of all hitting the sources.
+        let mut entry = self.entry.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(id) = self.fresh(&entry) {
+            return Ok(id);
+        }
+
+        let id = resolve()?;
+        let ttl = *self.ttl.read().unwrap_or_else(PoisonError::into_inner);
+        *entry = Some(Entry {
+            id,
+            expires: ttl.map(|ttl| Instant::now() + ttl),
+        });
+        Ok(id)
+    }
+
+    fn fresh(&self, entry: &Option<Entry>) -> Option<MachineId> {
+        let entry = entry.as_ref()?;
+        match entry.expires {
+            Some(expires) if Instant::now() >= expires => None,
+            _ => Some(entry.id),
+        }
+    }
+
+    fn invalidate(&self) {
+        *self.entry.write().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    fn set_ttl(&self, ttl: Option<Duration>) {
+        *self.ttl.write().unwrap_or_else(PoisonError::into_inner) = ttl;
+        self.invalidate();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn test_cached() {
+        assert_eq!(MachineId::cached().unwrap(), MachineId::new().unwrap());
+    }
+
+    #[test]
+    fn test_cache() {
+        let cache = Cache::new();
+        let reads = Cell::new(0);
+        let read = |value| {
+            reads.set(reads.get() + 1);
+            Ok(MachineId(Uuid::from_u128(value)))
+        };
+
+        assert_eq!(cache.get_or_resolve(|| read(1)).unwrap().0.as_u128(), 1);
+        assert_eq!(cache.get_or_resolve(|| read(2)).unwrap().0.as_u128(), 1);
+        assert_eq!(reads.get(), 1);
+
+        cache.invalidate();
+        assert_eq!(cache.get_or_resolve(|| read(2)).unwrap().0.as_u128(), 2);
+
+        cache.set_ttl(Some(Duration::ZERO));
+        assert_eq!(cache.get_or_resolve(|| read(3)).unwrap().0.as_u128(), 3);
+        assert_eq!(cache.get_or_resolve(|| read(4)).unwrap().0.as_u128(), 4);
+        assert_eq!(reads.get(), 4);
+    }
+
+    #[test]
+    fn test_errors_are_not_cached() {
+        let cache = Cache::new();
+        let error =
+            cache.get_or_resolve(|| Err(std::io::Error::from(std::io::ErrorKind::NotFound).into()));
+        assert!(error.is_err());
+
+        let id = cache.get_or_resolve(|| Ok(MachineId(Uuid::from_u128(1))));
+        assert_eq!(id.unwrap().0.as_u128(), 1);
+    }
+}
@@ -0,0 +1,222 @@
+//! Container detection and container-scoped identity.
+//!
+//! Inside a container `MachineId::new` returns either the host ID bind-mounted by the runtime,
+//! the ID baked into the image, or an error. [`detect`] tells whether the process runs
+//! in a container, and [`crate::sources::ContainerId`] provides an ID scoped to the container itself.
+
+use std::path::Path;
+
+use crate::sources::resolve_path;
+
+/// A container runtime recognized by [`detect`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub enum ContainerRuntime {
+    Docker,
+    Podman,
+    Kubernetes,
+    Containerd,
+    CriO,
+    Lxc,
+    SystemdNspawn,
+    /// A container announced via `container=` with an unknown value.
+    Other,
+}
+
+/// Detects the container runtime of the current process, if any.
+pub fn detect() -> Option<ContainerRuntime> {
+    detect_in("/")
+}
+
+/// Same as [`detect`], but reads `/proc`, `/run` and friends under `root`.
+pub fn detect_in(root: impl AsRef<Path>) -> Option<ContainerRuntime> {
+    let root = root.as_ref();
+    let read = |path: &str| std::fs::read(resolve_path(root, path.as_ref())).ok();
+    let exists = |path: &str| resolve_path(root, path.as_ref()).exists();
+
+    let cgroup = read("/proc/1/cgroup")
+        .or_else(|| read("/proc/self/cgroup"))
+        .map(|data| String::from_utf8_lossy(&data).into_owned())
+        .unwrap_or_default();
+
+    if cgroup.contains("kubepods") {
+        return Some(ContainerRuntime::Kubernetes);
+    }
+    if exists("/run/.containerenv") {
+        return Some(ContainerRuntime::Podman);
+    }
+    if exists("/.dockerenv") {
+        return Some(ContainerRuntime::Docker);
+    }
+
+    // systemd's convention, also followed by podman, LXC and systemd-nspawn.
+    let environ = read("/proc/1/environ").unwrap_or_default();
+    let announced = environ
+        .split(|b| *b == 0)
+        .find_map(|var| var.strip_prefix(b"container="));
+    if let Some(value) = announced {
+        return Some(match value {
+            b"docker" => ContainerRuntime::Docker,
+            b"podman" => ContainerRuntime::Podman,
+            b"lxc" | b"lxc-libvirt" => ContainerRuntime::Lxc,
+            b"systemd-nspawn" => ContainerRuntime::SystemdNspawn,
+            _ => ContainerRuntime::Other,
+        });
+    }
+
+    [
+        ("libpod", ContainerRuntime::Podman),
+        ("docker", ContainerRuntime::Docker),
+        ("cri-containerd", ContainerRuntime::Containerd),
+        ("crio", ContainerRuntime::CriO),
+        ("lxc.payload", ContainerRuntime::Lxc),
+        ("/lxc/", ContainerRuntime::Lxc),
+    ]
+    .into_iter()
+    .find(|(pattern, _)| cgroup.contains(pattern))
+    .map(|(_, runtime)| runtime)
+}
+
+/// The 64 hex character ID the runtime assigned to the current container.
+pub fn container_id() -> Option<String> {
+    container_id_in("/")
+}
+
+/// Same as [`container_id`], but reads `/proc` under `root`.
+///
+/// The ID is looked up in `/proc/self/cgroup`, which works with cgroup v1 and most cgroup v2 setups,
+/// and then in `/proc/self/mountinfo`, where runtimes bind-mount files like `hostname` from
+/// a per-container directory.
+pub fn container_id_in(root: impl AsRef<Path>) -> Option<String> {
+    let root = root.as_ref();
+    let read = |path: &str| std::fs::read_to_string(resolve_path(root, path.as_ref())).ok();
+
+    if let Some(id) = read("/proc/self/cgroup").and_then(|cgroup| find_id(cgroup.lines())) {
+        return Some(id);
+    }
+
+    read("/proc/self/mountinfo")
+        .and_then(|mountinfo| mountinfo.lines().find_map(mount_container_id))
+}
+
+/// The container ID in the source path of a mountinfo line, if it is a per-container directory:
+/// `/var/lib/docker/containers/<id>/` or podman's `.../overlay-containers/<id>/userdata/`.
+///
+/// Image layers like `/var/lib/docker/overlay2/<layer>/` or podman's
+/// `.../containers/storage/overlay/<layer>/` carry IDs of the same form, but are shared
+/// by all containers of an image.
+fn mount_container_id(line: &str) -> Option<String> {
+    // The fourth field is the path of the mount within its filesystem.
+    let path = line.split(' ').nth(3)?;
+    let components: Vec<&str> = path.split('/').collect();
+    components.windows(3).find_map(|window| {
+        let id = match window {
+            ["docker", "containers", id] => id,
+            ["overlay-containers", id, "userdata"] => id,
+            _ => return None,
+        };
+        is_container_id(id).then(|| id.to_ascii_lowercase())
+    })
+}
+
+fn is_container_id(token: &str) -> bool {
+    token.len() == 64 && token.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn find_id<'a>(mut lines: impl Iterator<Item = &'a str>) -> Option<String> {
+    lines.find_map(|line| {
+        line.split(|ch: char| !ch.is_ascii_alphanumeric())
+            .find(|token| is_container_id(token))
+            .map(str::to_ascii_lowercase)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempRoot;
+
+    const ID: &str = "4f2a6b1c9d8e7f60a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718";
+
+    #[test]
+    fn test_detect() {
+        let root = TempRoot::new();
+        assert_eq!(detect_in(root.path()), None);
+
+        root.write("proc/1/environ", b"PATH=/bin\0container=systemd-nspawn\0");
+        assert_eq!(
+            detect_in(root.path()),
+            Some(ContainerRuntime::SystemdNspawn)
+        );
+
+        root.write(".dockerenv", "");
+        assert_eq!(detect_in(root.path()), Some(ContainerRuntime::Docker));
+
+        root.write("run/.containerenv", "");
+        assert_eq!(detect_in(root.path()), Some(ContainerRuntime::Podman));
+
+        root.write(
+            "proc/1/cgroup",
+            format!("0::/kubepods/besteffort/pod1234/{ID}\n"),
+        );
+        assert_eq!(detect_in(root.path()), Some(ContainerRuntime::Kubernetes));
+
+        let root = TempRoot::new();
+        root.write(
+            "proc/1/cgroup",
+            format!("0::/system.slice/cri-containerd-{ID}.scope\n"),
+        );
+        assert_eq!(detect_in(root.path()), Some(ContainerRuntime::Containerd));
+    }
+
+    #[test]
+    fn test_container_id() {
+        let root = TempRoot::new();
+        assert_eq!(container_id_in(root.path()), None);
+
+        root.write("proc/self/cgroup", "0::/\n");
+        root.write(
+            "proc/self/mountinfo",
+            format!(
+                "612 523 0:52 / / rw - overlay overlay rw,upperdir=/var/lib/docker/overlay2/{}/diff\n\
+                 630 612 254:1 /var/lib/docker/containers/{ID}/hostname /etc/hostname rw - ext4 /dev/vda1 rw\n",
+                "a".repeat(64)
+            ),
+        );
+        assert_eq!(container_id_in(root.path()).as_deref(), Some(ID));
+
+        root.write(
+            "proc/self/cgroup",
+            format!("12:pids:/docker/{}\n", ID.to_ascii_uppercase()),
+        );
+        assert_eq!(container_id_in(root.path()).as_deref(), Some(ID));
+    }
+
+    #[test]
+    fn test_podman_cgroup_v2() {
+        let layer = "b".repeat(64);
+        for storage in [
+            "/var/lib/containers/storage",
+            "/home/user/.local/share/containers/storage",
+        ] {
+            let root = TempRoot::new();
+            root.write("proc/self/cgroup", "0::/\n");
+            root.write(
+                "proc/self/mountinfo",
+                format!(
+                    "1097 1014 0:61 / / rw,relatime - overlay overlay rw,lowerdir={storage}/overlay/l/ABC,upperdir={storage}/overlay/{layer}/diff\n\
+                     1098 1097 0:64 / /proc rw,nosuid,nodev,noexec,relatime - proc proc rw\n\
+                     1105 1097 0:25 {storage}/overlay/{layer}/merged/etc /mnt/layer rw - tmpfs tmpfs rw\n\
+                     1110 1097 0:25 {storage}/overlay-containers/{ID}/userdata/hostname /etc/hostname rw,nosuid,nodev - tmpfs tmpfs rw\n"
+                ),
+            );
+            assert_eq!(
+                container_id_in(root.path()).as_deref(),
+                Some(ID),
+                "{storage}"
+            );
+        }
+    }
+}
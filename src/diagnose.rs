@@ -0,0 +1,364 @@
+//! A structured report of all identity sources, for support requests like
+//! "why did two machines collide" or "why did the ID change after an upgrade".
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    builder::active_override,
+    container::{self, ContainerRuntime},
+    error::{Error, Location, RawContent, SourceError},
+    sources::{self, IdSource},
+    virtualization::{self, Virtualization},
+    Validator,
+};
+
+/// Options for [`diagnose_with`].
+#[derive(Debug, Clone)]
+pub struct DiagnoseOptions {
+    /// Mask all hex digits of the values but the first four, see [`RawContent`].
+    pub redact: bool,
+    /// The filesystem root file-based sources are resolved against.
+    pub root: PathBuf,
+}
+
+impl Default for DiagnoseOptions {
+    fn default() -> Self {
+        Self {
+            redact: true,
+            root: PathBuf::from("/"),
+        }
+    }
+}
+
+/// The result of [`diagnose`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Report {
+    /// `<arch>-<os>` of the current target.
+    pub target: String,
+    /// The source `MachineId::new` takes the ID from, if any.
+    pub selected: Option<String>,
+    pub sources: Vec<SourceReport>,
+    pub container: Option<ContainerRuntime>,
+    pub virtualization: Virtualization,
+}
+
+/// What a single source returned.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceReport {
+    pub name: String,
+    pub location: Option<Location>,
+    /// The ID, or the raw content that failed to parse. Redacted unless requested otherwise.
+    pub value: Option<String>,
+    /// Whether the ID was read, parsed and passed the [`Validator`].
+    pub valid: bool,
+    /// Why the source is not valid, `None` for an override that is not set.
+    pub error: Option<String>,
+    pub file: Option<FileMetadata>,
+    /// How long the read took, `None` on targets without a clock (e.g. wasm32) and for
+    /// an override that is not set.
+    pub duration: Option<Duration>,
+}
+
+/// Metadata of the file a source reads.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileMetadata {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub readonly: bool,
+    /// Unix permission bits.
+    pub mode: Option<u32>,
+    pub symlink_target: Option<PathBuf>,
+}
+
+/// Queries every known source on the current platform, see [`diagnose_with`].
+pub fn diagnose() -> Report {
+    diagnose_with(&DiagnoseOptions::default())
+}
+
+/// Queries every known source: the [`sources::Override`], the platform default and
+/// [`sources::register`]ed sources, and the opt-in hardware and container ones.
+pub fn diagnose_with(options: &DiagnoseOptions) -> Report {
+    let validator = Validator::new();
+    let override_source = sources::Override::new();
+    let mut sources = vec![match active_override(Some(&override_source)) {
+        Some(source) => check_source(source, &validator, options),
+        None => SourceReport {
+            name: override_source.name().to_owned(),
+            location: None,
+            value: None,
+            valid: false,
+            error: None,
+            file: None,
+            duration: None,
+        },
+    }];
+    sources.extend(
+        sources::default_sources()
+            .iter()
+            .map(|source| check_source(source.as_ref(), &validator, options)),
+    );
+
+    // What `MachineIdBuilder::new().resolve()` picks: a set override is the only source
+    // consulted, otherwise the first valid default source.
+    let selected = match sources.split_first() {
+        Some((override_report, _)) if override_report.is_set() => {
+            override_report.valid.then(|| override_report.name.clone())
+        }
+        Some((_, defaults)) => defaults
+            .iter()
+            .find(|source| source.valid)
+            .map(|source| source.name.clone()),
+        None => None,
+    };
+
+    let opt_in: Vec<Box<dyn IdSource>> = vec![
+        #[cfg(target_os = "linux")]
+        Box::new(sources::DmiProductUuid),
+        Box::new(sources::ContainerId),
+    ];
+    sources.extend(
+        opt_in
+            .iter()
+            .map(|source| check_source(source.as_ref(), &validator, options)),
+    );
+
+    Report {
+        target: crate::error::current_target(),
+        selected,
+        sources,
+        container: container::detect_in(&options.root),
+        virtualization: virtualization::detect_in(&options.root),
+    }
+}
+
+impl SourceReport {
+    /// Whether the source returned anything, which is only not the case for an unset override.
+    fn is_set(&self) -> bool {
+        self.valid || self.error.is_some()
+    }
+}
+
+fn check_source(
+    source: &dyn IdSource,
+    validator: &Validator,
+    options: &DiagnoseOptions,
+) -> SourceReport {
+    let location = source.location(&options.root);
+    let (result, duration) = timed(|| source.read(&options.root));
+
+    let (value, error) = match result {
+        Ok(uuid) => (
+            Some(RawContent::new(uuid.hyphenated().to_string())),
+            validator.validate(&uuid).err(),
+        ),
+        Err(error) => {
+            let raw = match &error {
+                Error::InvalidContent { raw, .. } => Some(raw.clone()),
+                _ => None,
+            };
+            (raw, Some(error))
+        }
+    };
+
+    let value = value.map(|raw| {
+        if options.redact {
+            raw.redacted()
+        } else {
+            raw.reveal().to_owned()
+        }
+    });
+
+    let error = error.map(|error| {
+        SourceError {
+            name: source.name().to_owned(),
+            location: location.clone(),
+            error: Box::new(error),
+        }
+        .to_string()
+    });
+
+    let file = match &location {
+        Some(Location::File(path)) => file_metadata(path),
+        _ => None,
+    };
+
+    SourceReport {
+        name: source.name().to_owned(),
+        location,
+        valid: value.is_some() && error.is_none(),
+        value,
+        error,
+        file,
+        duration,
+    }
+}
+
+/// Runs `f`, measuring how long it takes where `Instant` is available.
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Option<Duration>) {
+    #[cfg(any(unix, windows))]
+    {
+        let started = std::time::Instant::now();
+        let value = f();
+        (value, Some(started.elapsed()))
+    }
+    // `Instant::now` panics on wasm32-unknown-unknown.
+    #[cfg(not(any(unix, windows)))]
+    (f(), None)
+}
+
+fn file_metadata(path: &Path) -> Option<FileMetadata> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let symlink_target = std::fs::symlink_metadata(path)
+        .ok()
+        .filter(|metadata| metadata.file_type().is_symlink())
+        .and_then(|_| std::fs::read_link(path).ok());
+
+    #[cfg(unix)]
+    let mode = Some(std::os::unix::fs::PermissionsExt::mode(&metadata.permissions()) & 0o7777);
+    #[cfg(not(unix))]
+    let mode = None;
+
+    Some(FileMetadata {
+        size: metadata.len(),
+        modified: metadata.modified().ok(),
+        readonly: metadata.permissions().readonly(),
+        mode,
+        symlink_target,
+    })
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "target: {}", self.target)?;
+        writeln!(
+            f,
+            "selected: {}",
+            self.selected.as_deref().unwrap_or("none")
+        )?;
+        writeln!(
+            f,
+            "container: {}",
+            self.container
+                .map(|runtime| format!("{runtime:?}"))
+                .unwrap_or_else(|| "none".to_owned())
+        )?;
+        writeln!(
+            f,
+            "virtualization: {}, clone risk {:?}",
+            self.virtualization
+                .hypervisor
+                .map(|hypervisor| format!("{hypervisor:?}"))
+                .unwrap_or_else(|| "none".to_owned()),
+            self.virtualization.clone_risk
+        )?;
+
+        for source in &self.sources {
+            write!(
+                f,
+                "\n[{}] {}",
+                source.name,
+                match (source.valid, source.is_set()) {
+                    (true, _) => "valid",
+                    (false, true) => "invalid",
+                    (false, false) => "not set",
+                }
+            )?;
+            if let Some(location) = &source.location {
+                write!(f, "\n  location: {location}")?;
+            }
+            if let Some(value) = &source.value {
+                write!(f, "\n  value: {value}")?;
+            }
+            if let Some(error) = &source.error {
+                write!(f, "\n  error: {error}")?;
+            }
+            if let Some(file) = &source.file {
+                write!(f, "\n  file: {} bytes", file.size)?;
+                if let Some(mode) = file.mode {
+                    write!(f, ", mode {mode:o}")?;
+                }
+                if let Some(target) = &file.symlink_target {
+                    write!(f, ", -> {}", target.display())?;
+                }
+            }
+            if let Some(duration) = source.duration {
+                write!(f, "\n  time: {duration:?}")?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempRoot;
+
+    #[test]
+    fn test_diagnose() {
+        let root = TempRoot::new();
+        root.write("etc/machine-id", "3d1219c7c4c5404aaa1f6d2a48adfda4\n");
+        root.write("var/lib/dbus/machine-id", "not an id\n");
+
+        let report = diagnose_with(&DiagnoseOptions {
+            root: root.path().to_owned(),
+            ..Default::default()
+        });
+        let source = |name: &str| report.sources.iter().find(|s| s.name == name);
+
+        if cfg!(target_os = "linux") {
+            assert_eq!(report.selected.as_deref(), Some("machine-id"));
+
+            let machine_id = source("machine-id").unwrap();
+            assert!(machine_id.valid);
+            assert_eq!(
+                machine_id.value.as_deref(),
+                Some("3d12****-****-****-****-************")
+            );
+            assert_eq!(machine_id.file.as_ref().unwrap().size, 33);
+            assert!(machine_id.duration.is_some());
+
+            let dbus = source("dbus").unwrap();
+            assert!(!dbus.valid);
+            assert_eq!(dbus.value.as_deref(), Some("not an id?"));
+            assert!(dbus.error.as_ref().unwrap().starts_with("dbus ("));
+
+            assert!(source("run").unwrap().file.is_none());
+        }
+
+        let override_source = source("override").unwrap();
+        assert!(!override_source.valid);
+        assert_eq!(override_source.error, None);
+        assert_eq!(override_source.duration, None);
+
+        let report = diagnose_with(&DiagnoseOptions {
+            redact: false,
+            root: root.path().to_owned(),
+        });
+        if cfg!(target_os = "linux") {
+            let machine_id = report.sources.iter().find(|s| s.name == "machine-id");
+            assert_eq!(
+                machine_id.unwrap().value.as_deref(),
+                Some("3d1219c7-c4c5-404a-aa1f-6d2a48adfda4")
+            );
+        }
+        assert!(report.to_string().contains("[override] not set\n"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let report = diagnose();
+        let json = serde_json::to_string(&report).unwrap();
+        let de: Report = serde_json::from_str(&json).unwrap();
+        assert_eq!(de.sources.len(), report.sources.len());
+    }
+}
@@ -0,0 +1,371 @@
+//! DMI / SMBIOS hardware identifiers.
+//!
+//! Unlike the machine ID, these survive OS reinstalls. On Linux they are read from
+//! `/sys/class/dmi/id` with a fallback to the raw SMBIOS tables in `/sys/firmware/dmi/tables`.
+//! Both are usually readable by root only.
+
+use uuid::Uuid;
+
+use crate::Result;
+
+/// Serial numbers firmware vendors leave in place of real values.
+const PLACEHOLDER_SERIALS: &[&str] = &[
+    "to be filled by o.e.m.",
+    "to be filled by oem",
+    "default string",
+    "system serial number",
+    "chassis serial number",
+    "base board serial number",
+    "not specified",
+    "not applicable",
+    "not available",
+    "not settable",
+    "not present",
+    "none",
+    "n/a",
+    "na",
+    "oem",
+    "o.e.m.",
+    "serial",
+    "0123456789",
+    "123456789",
+    "1234567890",
+];
+
+/// System UUIDs shared by many boards with unprogrammed firmware.
+const PLACEHOLDER_UUIDS: &[u128] = &[
+    0x00000000_0000_0000_0000_000000000000,
+    0xffffffff_ffff_ffff_ffff_ffffffffffff,
+    0x03000200_0400_0500_0006_000700080009,
+    0x00020003_0004_0005_0006_000700080009,
+];
+
+/// Hardware identifiers from the SMBIOS System (type 1), Baseboard (type 2)
+/// and Chassis (type 3) structures. Placeholder values are reported as `None`.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dmi {
+    pub product_uuid: Option<Uuid>,
+    pub product_serial: Option<String>,
+    pub board_serial: Option<String>,
+    pub chassis_serial: Option<String>,
+}
+
+impl Dmi {
+    /// Reads the identifiers of the running system.
+    #[cfg(target_os = "linux")]
+    pub fn read() -> Result<Self> {
+        Self::from_root("/")
+    }
+
+    /// Reads the identifiers from `sys` mounted under `root`.
+    ///
+    /// Values missing from `/sys/class/dmi/id` are looked up in the raw SMBIOS tables.
+    pub fn from_root(root: impl AsRef<std::path::Path>) -> Result<Self> {
+        use crate::sources::resolve_path;
+
+        let root = root.as_ref();
+        let read = |name: &str| {
+            let path = resolve_path(root, format!("/sys/class/dmi/id/{name}").as_ref());
+            std::fs::read_to_string(path).ok()
+        };
+
+        let mut dmi = Dmi {
+            product_uuid: read("product_uuid").and_then(|uuid| parse_uuid(&uuid)),
+            product_serial: read("product_serial").and_then(|serial| sanitize_serial(&serial)),
+            board_serial: read("board_serial").and_then(|serial| sanitize_serial(&serial)),
+            chassis_serial: read("chassis_serial").and_then(|serial| sanitize_serial(&serial)),
+        };
+
+        if !dmi.is_complete() {
+            let tables = std::fs::read(resolve_path(
+                root,
+                "/sys/firmware/dmi/tables/smbios_entry_point".as_ref(),
+            ))
+            .and_then(|entry_point| {
+                let tables =
+                    std::fs::read(resolve_path(root, "/sys/firmware/dmi/tables/DMI".as_ref()))?;
+                Ok((entry_point, tables))
+            });
+
+            let parsed = tables
+                .map_err(crate::error::Error::from)
+                .and_then(|(entry_point, tables)| Self::parse_smbios(&entry_point, &tables));
+            match parsed {
+                Ok(parsed) => dmi.merge(parsed),
+                // The raw tables only fill gaps, sysfs values are kept when they are unusable.
+                Err(error) if dmi.is_empty() => return Err(error),
+                Err(_) => {}
+            }
+        }
+
+        Ok(dmi)
+    }
+
+    /// Parses a raw SMBIOS entry point and structure table.
+    ///
+    /// The entry point is only used for the SMBIOS version: starting with 2.6 the first
+    /// three fields of the system UUID are stored little-endian.
+    pub fn parse_smbios(entry_point: &[u8], tables: &[u8]) -> Result<Self> {
+        let version = smbios_version(entry_point).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "unknown SMBIOS entry point",
+            )
+        })?;
+
+        let mut dmi = Dmi::default();
+        let mut offset = 0;
+        while offset + 4 <= tables.len() {
+            let kind = tables[offset];
+            let length = tables[offset + 1] as usize;
+            if length < 4 || offset + length > tables.len() {
+                break;
+            }
+
+            let formatted = &tables[offset..offset + length];
+            let strings_start = offset + length;
+            let mut strings_end = strings_start;
+            while strings_end + 1 < tables.len()
+                && (tables[strings_end] != 0 || tables[strings_end + 1] != 0)
+            {
+                strings_end += 1;
+            }
+            let strings = &tables[strings_start..strings_end.min(tables.len())];
+            let string_at = |index: usize| {
+                formatted
+                    .get(index)
+                    .and_then(|n| smbios_string(strings, *n))
+                    .and_then(sanitize_serial)
+            };
+
+            match kind {
+                1 => {
+                    dmi.product_serial = string_at(0x07);
+                    if let Some(bytes) = formatted.get(0x08..0x18) {
+                        let bytes: uuid::Bytes = bytes.try_into().expect("16 bytes");
+                        let uuid = if version >= (2, 6) {
+                            Uuid::from_bytes_le(bytes)
+                        } else {
+                            Uuid::from_bytes(bytes)
+                        };
+                        dmi.product_uuid = (!is_placeholder_uuid(&uuid)).then_some(uuid);
+                    }
+                }
+                2 if dmi.board_serial.is_none() => dmi.board_serial = string_at(0x07),
+                3 if dmi.chassis_serial.is_none() => dmi.chassis_serial = string_at(0x07),
+                127 => break,
+                _ => {}
+            }
+
+            offset = strings_end + 2;
+        }
+
+        Ok(dmi)
+    }
+
+    fn is_complete(&self) -> bool {
+        self.product_uuid.is_some()
+            && self.product_serial.is_some()
+            && self.board_serial.is_some()
+            && self.chassis_serial.is_some()
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Dmi::default()
+    }
+
+    fn merge(&mut self, other: Dmi) {
+        self.product_uuid = self.product_uuid.or(other.product_uuid);
+        self.product_serial = self.product_serial.take().or(other.product_serial);
+        self.board_serial = self.board_serial.take().or(other.board_serial);
+        self.chassis_serial = self.chassis_serial.take().or(other.chassis_serial);
+    }
+}
+
+/// Whether `uuid` is a well-known value of unprogrammed firmware, e.g. all zeros or all `FF`.
+pub fn is_placeholder_uuid(uuid: &Uuid) -> bool {
+    PLACEHOLDER_UUIDS.contains(&uuid.as_u128())
+}
+
+/// Whether `serial` is a filler such as `To Be Filled By O.E.M.` rather than a real serial number.
+pub fn is_placeholder_serial(serial: &str) -> bool {
+    let serial = serial.trim();
+    let mut chars = serial.chars();
+    let repeated = match chars.next() {
+        Some(first) => chars.all(|ch| ch == first),
+        None => true,
+    };
+
+    repeated
+        || PLACEHOLDER_SERIALS
+            .iter()
+            .any(|placeholder| serial.eq_ignore_ascii_case(placeholder))
+}
+
+fn sanitize_serial(serial: &str) -> Option<String> {
+    let serial = serial.trim();
+    (!is_placeholder_serial(serial)).then(|| serial.to_owned())
+}
+
+fn parse_uuid(uuid: &str) -> Option<Uuid> {
+    Uuid::parse_str(uuid.trim())
+        .ok()
+        .filter(|uuid| !is_placeholder_uuid(uuid))
+}
+
+fn smbios_version(entry_point: &[u8]) -> Option<(u8, u8)> {
+    if entry_point.starts_with(b"_SM3_") {
+        Some((*entry_point.get(0x07)?, *entry_point.get(0x08)?))
+    } else if entry_point.starts_with(b"_SM_") {
+        Some((*entry_point.get(0x06)?, *entry_point.get(0x07)?))
+    } else if entry_point.starts_with(b"_DMI_") {
+        let bcd = *entry_point.get(0x0E)?;
+        Some((bcd >> 4, bcd & 0x0F))
+    } else {
+        None
+    }
+}
+
+fn smbios_string(strings: &[u8], index: u8) -> Option<&str> {
+    let index = (index as usize).checked_sub(1)?;
+    let bytes = strings.split(|b| *b == 0).nth(index)?;
+    std::str::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW_UUID: [u8; 16] = [
+        0x78, 0x56, 0x34, 0x12, 0x34, 0x12, 0x78, 0x56, //
+        0x9a, 0xbc, 0xde, 0xf0, 0x12, 0x34, 0x56, 0x78,
+    ];
+
+    fn entry_point(major: u8, minor: u8) -> Vec<u8> {
+        let mut entry_point = b"_SM3_".to_vec();
+        entry_point.extend([0, 0x18, major, minor, 0]);
+        entry_point
+    }
+
+    fn structure(kind: u8, formatted: &[u8], strings: &[&str]) -> Vec<u8> {
+        let mut data = vec![kind, 4 + formatted.len() as u8, 0, 0];
+        data.extend(formatted);
+        for string in strings {
+            data.extend(string.as_bytes());
+            data.push(0);
+        }
+        if strings.is_empty() {
+            data.push(0);
+        }
+        data.push(0);
+        data
+    }
+
+    fn tables(uuid: [u8; 16], serial: &str) -> Vec<u8> {
+        // type 1: manufacturer, product name, version, serial number, UUID
+        let mut system = vec![1, 2, 3, 4];
+        system.extend(uuid);
+        let mut tables = structure(1, &system, &["Vendor", "Product", "1.0", serial]);
+        tables.extend(structure(2, &[1, 2, 3, 1], &["BSN-42"]));
+        tables.extend(structure(
+            3,
+            &[1, 0, 2, 0],
+            &["Vendor", "To Be Filled By O.E.M."],
+        ));
+        tables.extend(structure(127, &[], &[]));
+        tables
+    }
+
+    #[test]
+    fn test_parse_smbios() {
+        let dmi = Dmi::parse_smbios(&entry_point(3, 2), &tables(RAW_UUID, "SN-1")).unwrap();
+        assert_eq!(
+            dmi,
+            Dmi {
+                product_uuid: Some(Uuid::from_u128(0x12345678_1234_5678_9abc_def012345678)),
+                product_serial: Some("SN-1".to_owned()),
+                board_serial: Some("BSN-42".to_owned()),
+                chassis_serial: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_uuid_byte_order_before_2_6() {
+        let dmi = Dmi::parse_smbios(&entry_point(2, 4), &tables(RAW_UUID, "SN-1")).unwrap();
+        assert_eq!(
+            dmi.product_uuid,
+            Some(Uuid::from_u128(0x78563412_3412_7856_9abc_def012345678))
+        );
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let dmi = Dmi::parse_smbios(&entry_point(3, 0), &tables([0xff; 16], "0000000")).unwrap();
+        assert_eq!(dmi.product_uuid, None);
+        assert_eq!(dmi.product_serial, None);
+
+        assert!(is_placeholder_serial("To be filled by O.E.M."));
+        assert!(is_placeholder_serial("  Default string "));
+        assert!(is_placeholder_serial(""));
+        assert!(!is_placeholder_serial("PF2ABCDE"));
+        assert!(is_placeholder_uuid(&Uuid::nil()));
+        assert!(is_placeholder_uuid(&Uuid::max()));
+        assert!(!is_placeholder_uuid(&Uuid::from_u128(1)));
+
+        assert!(Dmi::parse_smbios(b"garbage", &[]).is_err());
+    }
+
+    #[test]
+    fn test_from_root() {
+        let root = crate::test_util::TempRoot::new();
+        root.write(
+            "sys/class/dmi/id/product_uuid",
+            "ec2a1b2c-3d4e-5f60-7182-93a4b5c6d7e8\n",
+        );
+        root.write("sys/class/dmi/id/product_serial", "Default string\n");
+        root.write(
+            "sys/firmware/dmi/tables/smbios_entry_point",
+            entry_point(3, 2),
+        );
+        root.write("sys/firmware/dmi/tables/DMI", tables(RAW_UUID, "SN-1"));
+
+        let dmi = Dmi::from_root(root.path()).unwrap();
+        assert_eq!(
+            dmi.product_uuid,
+            Some(Uuid::from_u128(0xec2a1b2c_3d4e_5f60_7182_93a4b5c6d7e8))
+        );
+        assert_eq!(dmi.product_serial.as_deref(), Some("SN-1"));
+        assert_eq!(dmi.board_serial.as_deref(), Some("BSN-42"));
+        assert_eq!(dmi.chassis_serial, None);
+
+        let empty = crate::test_util::TempRoot::new();
+        assert!(Dmi::from_root(empty.path()).is_err());
+    }
+
+    #[test]
+    fn test_from_root_garbage_tables() {
+        let root = crate::test_util::TempRoot::new();
+        root.write(
+            "sys/class/dmi/id/product_uuid",
+            "ec2a1b2c-3d4e-5f60-7182-93a4b5c6d7e8\n",
+        );
+        root.write("sys/class/dmi/id/board_serial", "BSN-42\n");
+        root.write("sys/firmware/dmi/tables/smbios_entry_point", "garbage");
+        root.write("sys/firmware/dmi/tables/DMI", "garbage");
+
+        let dmi = Dmi::from_root(root.path()).unwrap();
+        assert_eq!(
+            dmi.product_uuid,
+            Some(Uuid::from_u128(0xec2a1b2c_3d4e_5f60_7182_93a4b5c6d7e8))
+        );
+        assert_eq!(dmi.board_serial.as_deref(), Some("BSN-42"));
+        assert_eq!(dmi.product_serial, None);
+
+        let root = crate::test_util::TempRoot::new();
+        root.write("sys/firmware/dmi/tables/smbios_entry_point", "garbage");
+        root.write("sys/firmware/dmi/tables/DMI", "garbage");
+        assert!(Dmi::from_root(root.path()).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+//! Crockford base32 and base64url codecs for 128-bit IDs.
+
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+/// Crockford's check symbol alphabet, the base32 symbols followed by five for values 32-36.
+const CROCKFORD_CHECK: &[u8; 37] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ*~$=U";
+const BASE64URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encodes `value` as `len` Crockford base32 symbols, most significant first.
+pub(crate) fn crockford_encode(value: u128, len: usize) -> String {
+    (0..len)
+        .rev()
+        .map(|i| CROCKFORD[((value >> (5 * i)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+/// The value of a Crockford base32 symbol, accepting lowercase and the
+/// commonly confused `I`, `L` (as `1`) and `O` (as `0`).
+pub(crate) fn crockford_value(symbol: char) -> Option<u8> {
+    let symbol = match symbol.to_ascii_uppercase() {
+        'I' | 'L' => '1',
+        'O' => '0',
+        symbol => symbol,
+    };
+    CROCKFORD
+        .iter()
+        .position(|ch| *ch as char == symbol)
+        .map(|value| value as u8)
+}
+
+/// Decodes Crockford base32 symbols, ignoring hyphens.
+pub(crate) fn crockford_decode(symbols: &str) -> Option<u128> {
+    symbols
+        .chars()
+        .filter(|ch| *ch != '-')
+        .try_fold(0u128, |value, ch| {
+            let digit = crockford_value(ch)?;
+            value.checked_mul(32).map(|value| value | u128::from(digit))
+        })
+}
+
+/// Crockford's check symbol for `value`, which detects any single wrong symbol
+/// and any transposition of two adjacent symbols.
+pub(crate) fn crockford_check(value: u128) -> char {
+    CROCKFORD_CHECK[(value % 37) as usize] as char
+}
+
+/// The value of a check symbol, with the same aliases as [`crockford_value`].
+pub(crate) fn crockford_check_value(symbol: char) -> Option<u8> {
+    match symbol.to_ascii_uppercase() {
+        symbol @ ('*' | '~' | '$' | '=' | 'U') => CROCKFORD_CHECK
+            .iter()
+            .position(|ch| *ch as char == symbol)
+            .map(|value| value as u8),
+        symbol => crockford_value(symbol),
+    }
+}
+
+/// Encodes bytes as unpadded base64url.
+pub(crate) fn base64url_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 4).div_ceil(3));
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            encoded.push(BASE64URL[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    encoded
+}
+
+/// Decodes unpadded base64url.
+pub(crate) fn base64url_decode(encoded: &str) -> Option<Vec<u8>> {
+    if encoded.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    for chunk in encoded.as_bytes().chunks(4) {
+        let n = chunk.iter().enumerate().try_fold(0u32, |n, (i, ch)| {
+            let value = BASE64URL.iter().position(|b| b == ch)? as u32;
+            Some(n | value << (18 - 6 * i))
+        })?;
+        for i in 0..chunk.len() - 1 {
+            bytes.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crockford() {
+        let value = 0x3d1219c7c4c5404aaa1f6d2a48adfda4;
+        let encoded = crockford_encode(value, 26);
+        assert_eq!(encoded, "1X28CWFH65815AM7VD594AVZD4");
+        assert_eq!(crockford_decode(&encoded), Some(value));
+        assert_eq!(crockford_decode("1x28cwfh658l5am7vd594avzd4"), Some(value));
+        assert_eq!(
+            crockford_decode("1X28-CWFH-6581"),
+            crockford_decode("1X28CWFH6581")
+        );
+        assert_eq!(crockford_decode("U"), None);
+
+        assert_eq!(crockford_check(value), 'M');
+        assert_eq!(crockford_check(32), '*');
+        assert_eq!(crockford_check_value('u'), Some(36));
+        assert_eq!(crockford_check_value('o'), Some(0));
+    }
+
+    #[test]
+    fn test_base64url() {
+        let bytes = 0x3d1219c7c4c5404aaa1f6d2a48adfda4u128.to_be_bytes();
+        let encoded = base64url_encode(&bytes);
+        assert_eq!(encoded, "PRIZx8TFQEqqH20qSK39pA");
+        assert_eq!(base64url_decode(&encoded).as_deref(), Some(&bytes[..]));
+        assert_eq!(base64url_decode("PRIZx8TFQEqqH20qSK39p+"), None);
+    }
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -7,4 +9,230 @@ pub enum Error {
 
     #[error(transparent)]
     IoError(#[from] std::io::Error),
+
+    /// The content read from a source is not a valid ID.
+    #[error("invalid machine ID `{raw}`: {error}")]
+    InvalidContent {
+        raw: RawContent,
+        #[source]
+        error: uuid::Error,
+    },
+
+    /// The ID file is empty or contains systemd's `uninitialized` first boot marker.
+    #[error("machine ID in {} is not initialized yet", path.display())]
+    Uninitialized { path: PathBuf },
+
+    #[error("suspicious machine ID {value}: {reason}")]
+    SuspiciousId { value: uuid::Uuid, reason: String },
+
+    /// The target has no built-in machine ID sources and none were registered.
+    #[error("machine ID is not supported on {target}")]
+    Unsupported { target: String },
+
+    /// Every source failed, in the order they were tried.
+    #[error("{}", AllSourcesFailed(.0))]
+    AllSourcesFailed(Vec<SourceError>),
+
+    #[error("invalid fingerprint: {0}")]
+    InvalidFingerprint(String),
+
+    /// Text that is not a machine ID in any [`crate::Format`], or an unknown format name.
+    #[error("{0}")]
+    InvalidFormat(String),
+
+    #[error("invalid support code: {0}")]
+    InvalidSupportCode(String),
+}
+
+impl Error {
+    /// Per-source errors of [`Error::AllSourcesFailed`], empty for other errors.
+    pub fn source_errors(&self) -> &[SourceError] {
+        match self {
+            Error::AllSourcesFailed(errors) => errors,
+            _ => &[],
+        }
+    }
+}
+
+/// `<arch>-<os>` of the current target, e.g. `wasm32-unknown`.
+pub(crate) fn current_target() -> String {
+    let os = match std::env::consts::OS {
+        "" => "unknown",
+        os => os,
+    };
+    format!("{}-{os}", std::env::consts::ARCH)
+}
+
+/// Why a single source failed to provide the ID.
+#[derive(Debug, Error)]
+#[error("{name}{}: {error}", location.as_ref().map(|location| format!(" ({location})")).unwrap_or_default())]
+pub struct SourceError {
+    /// The name of the source, see [`crate::sources::IdSource::name`].
+    pub name: String,
+    /// Where the source looked for the ID, if applicable.
+    pub location: Option<Location>,
+    #[source]
+    pub error: Box<Error>,
+}
+
+impl SourceError {
+    /// The OS error code, if the source failed with an I/O error.
+    pub fn os_error(&self) -> Option<i32> {
+        match self.error.as_ref() {
+            Error::IoError(error) => error.raw_os_error(),
+            _ => None,
+        }
+    }
+
+    /// The content that failed to parse, if any.
+    pub fn raw(&self) -> Option<&RawContent> {
+        match self.error.as_ref() {
+            Error::InvalidContent { raw, .. } => Some(raw),
+            _ => None,
+        }
+    }
+}
+
+/// Where a source looks for the ID.
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum Location {
+    File(PathBuf),
+    EnvVar(String),
+    RegistryValue(String),
+    Sysctl(String),
+    IoRegistry(String),
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::File(path) => write!(f, "{}", path.display()),
+            Location::EnvVar(var) => write!(f, "${var}"),
+            Location::RegistryValue(value) => f.write_str(value),
+            Location::Sysctl(name) => write!(f, "sysctl {name}"),
+            Location::IoRegistry(property) => write!(f, "IOKit {property}"),
+        }
+    }
+}
+
+/// Content read from a source.
+///
+/// Machine IDs are confidential, so `Display` and `Debug` mask all hex digits but the first four,
+/// which still shows what went wrong with the format. Use [`RawContent::reveal`] for the original.
+#[derive(PartialEq, Eq, Clone)]
+pub struct RawContent(String);
+
+impl RawContent {
+    const MAX_DISPLAY_LEN: usize = 80;
+
+    pub fn new(content: impl Into<String>) -> Self {
+        Self(content.into())
+    }
+
+    pub fn reveal(&self) -> &str {
+        &self.0
+    }
+
+    /// The masked form used by `Display`.
+    pub fn redacted(&self) -> String {
+        let mut hex_digits = 0;
+        let mut redacted: String = self
+            .0
+            .chars()
+            .take(Self::MAX_DISPLAY_LEN)
+            .map(|ch| {
+                if ch.is_ascii_hexdigit() {
+                    hex_digits += 1;
+                    if hex_digits > 4 {
+                        return '*';
+                    }
+                }
+                if ch.is_control() {
+                    '?'
+                } else {
+                    ch
+                }
+            })
+            .collect();
+
+        if self.0.chars().count() > Self::MAX_DISPLAY_LEN {
+            redacted.push('…');
+        }
+        redacted
+    }
+}
+
+impl std::fmt::Display for RawContent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.redacted())
+    }
+}
+
+impl std::fmt::Debug for RawContent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RawContent({:?})", self.redacted())
+    }
+}
+
+struct AllSourcesFailed<'a>(&'a [SourceError]);
+
+impl std::fmt::Display for AllSourcesFailed<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("no machine ID sources configured");
+        }
+
+        f.write_str("all machine ID sources failed: ")?;
+        for (i, error) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{error}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_content_is_redacted() {
+        let raw = RawContent::new("3d1219c7-c4c5-404a-aa1f-6d2a48adfda4x\n");
+        assert_eq!(raw.to_string(), "3d12****-****-****-****-************x?");
+        assert_eq!(raw.reveal(), "3d1219c7-c4c5-404a-aa1f-6d2a48adfda4x\n");
+        assert!(!format!("{raw:?}").contains("19c7"));
+    }
+
+    #[test]
+    fn test_all_sources_failed_display() {
+        let error = Error::AllSourcesFailed(vec![
+            SourceError {
+                name: "machine-id".to_owned(),
+                location: Some(Location::File("/etc/machine-id".into())),
+                error: Box::new(std::io::Error::from_raw_os_error(2).into()),
+            },
+            SourceError {
+                name: "env:ID".to_owned(),
+                location: None,
+                error: Box::new(Error::Uninitialized {
+                    path: "/run/machine-id".into(),
+                }),
+            },
+        ]);
+
+        let text = error.to_string();
+        assert!(text.starts_with("all machine ID sources failed: machine-id (/etc/machine-id): "));
+        assert!(text.ends_with("; env:ID: machine ID in /run/machine-id is not initialized yet"));
+        assert_eq!(error.source_errors()[0].os_error(), Some(2));
+
+        assert_eq!(
+            Error::AllSourcesFailed(Vec::new()).to_string(),
+            "no machine ID sources configured"
+        );
+    }
 }
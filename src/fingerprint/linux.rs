@@ -0,0 +1,128 @@
+use std::{
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{dmi::Dmi, sources::resolve_path};
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    let value = fs::read_to_string(path).ok()?;
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_owned())
+}
+
+fn sysfs(root: &Path, path: &str) -> PathBuf {
+    resolve_path(root, path.as_ref())
+}
+
+/// Unicast MAC addresses of interfaces backed by a device, sorted and comma separated.
+pub fn mac_addresses(root: &Path) -> Option<String> {
+    let mut addresses: Vec<String> = fs::read_dir(sysfs(root, "/sys/class/net"))
+        .ok()?
+        .filter_map(|entry| {
+            let interface = entry.ok()?.path();
+            // Virtual interfaces (bridges, veth, tun) have no backing device,
+            // and randomized addresses (`NET_ADDR_RANDOM`) change on every boot.
+            if !interface.join("device").exists()
+                || read_trimmed(&interface.join("addr_assign_type")).as_deref() == Some("1")
+            {
+                return None;
+            }
+
+            let address = read_trimmed(&interface.join("address"))?.to_ascii_lowercase();
+            is_stable_mac(&address).then_some(address)
+        })
+        .collect();
+
+    addresses.sort();
+    addresses.dedup();
+    (!addresses.is_empty()).then(|| addresses.join(","))
+}
+
+fn is_stable_mac(address: &str) -> bool {
+    let octets: Vec<u8> = address
+        .split(':')
+        .filter_map(|octet| u8::from_str_radix(octet, 16).ok())
+        .collect();
+
+    octets.len() == 6 && octets.iter().any(|octet| *octet != 0) && octets[0] & 0x01 == 0
+}
+
+pub fn dmi_product_uuid(root: &Path) -> Option<String> {
+    let uuid = Dmi::from_root(root).ok()?.product_uuid?;
+    Some(uuid.hyphenated().to_string())
+}
+
+pub fn board_serial(root: &Path) -> Option<String> {
+    Dmi::from_root(root).ok()?.board_serial
+}
+
+/// The UUID of the filesystem `root` lives on, looked up in `/dev/disk/by-uuid`.
+pub fn root_fs_uuid(root: &Path) -> Option<String> {
+    let device = fs::metadata(root).ok()?.dev();
+
+    fs::read_dir(sysfs(root, "/dev/disk/by-uuid"))
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find(|entry| {
+            fs::metadata(entry.path())
+                .map(|metadata| metadata.rdev() == device)
+                .unwrap_or(false)
+        })
+        .map(|entry| entry.file_name().to_string_lossy().to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        fingerprint::{Component, FingerprintBuilder},
+        test_util::TempRoot,
+    };
+
+    #[test]
+    fn test_collect_from_root() {
+        let root = TempRoot::new();
+        root.write("etc/machine-id", "3d1219c7c4c5404aaa1f6d2a48adfda4\n");
+        root.write("sys/class/net/lo/address", "00:00:00:00:00:00\n");
+        root.write("sys/class/net/eth1/address", "52:54:00:AB:CD:EF\n");
+        root.write("sys/class/net/eth1/device/vendor", "0x1af4\n");
+        root.write("sys/class/net/eth0/address", "52:54:00:12:34:56\n");
+        root.write("sys/class/net/eth0/device/vendor", "0x1af4\n");
+        root.write("sys/class/net/wlan0/address", "06:54:00:12:34:56\n");
+        root.write("sys/class/net/wlan0/addr_assign_type", "1\n");
+        root.write("sys/class/net/wlan0/device/vendor", "0x8086\n");
+        root.write("sys/class/net/docker0/address", "02:42:ac:11:00:02\n");
+        root.write("sys/class/dmi/id/board_serial", "  \n");
+        root.write(
+            "sys/class/dmi/id/product_uuid",
+            "EC2A1B2C-3D4E-5F60-7182-93A4B5C6D7E8\n",
+        );
+
+        let fingerprint = FingerprintBuilder::new()
+            .root(root.path())
+            .collect()
+            .unwrap();
+        assert_eq!(
+            fingerprint.get(Component::MachineId),
+            Some("3d1219c7c4c5404aaa1f6d2a48adfda4")
+        );
+        assert_eq!(
+            fingerprint.get(Component::MacAddresses),
+            Some("52:54:00:12:34:56,52:54:00:ab:cd:ef")
+        );
+        assert_eq!(
+            fingerprint.get(Component::DmiProductUuid),
+            Some("ec2a1b2c-3d4e-5f60-7182-93a4b5c6d7e8")
+        );
+        assert_eq!(fingerprint.get(Component::BoardSerial), None);
+
+        let mac_only = FingerprintBuilder::empty()
+            .component(Component::MacAddresses)
+            .root(root.path())
+            .collect()
+            .unwrap();
+        assert_eq!(mac_only.components().count(), 1);
+        assert_ne!(mac_only.id(b"key"), fingerprint.id(b"key"));
+    }
+}
@@ -0,0 +1,282 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use super::{Component, Fingerprint};
+use crate::{app_specific, error::Error};
+
+const COMPONENT_DOMAIN: &[u8] = b"yamid.fingerprint.component.v2";
+const FORMAT_PREFIX: &str = "yamid-fp2:";
+
+/// A [`Fingerprint`] with every component hashed separately, see [`Fingerprint::hashed`].
+///
+/// Unlike [`Fingerprint::id`] it still allows telling which components changed,
+/// see [`Fingerprint::matches`].
+///
+/// The hashes are HMACs keyed with an application key. Components like MAC addresses
+/// and serial numbers have little entropy, so anyone who knows the key can recover them
+/// by brute force: keep the key secret if the hashes are stored where others can read them.
+///
+/// The textual form is `yamid-fp2:<component>=<hash>,<component>=<hash>...`.
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HashedFingerprint {
+    components: BTreeMap<Component, String>,
+}
+
+impl HashedFingerprint {
+    pub fn get(&self, component: Component) -> Option<&str> {
+        self.components.get(&component).map(String::as_str)
+    }
+
+    pub fn components(&self) -> impl Iterator<Item = (Component, &str)> {
+        self.components
+            .iter()
+            .map(|(component, hash)| (*component, hash.as_str()))
+    }
+}
+
+impl std::fmt::Display for HashedFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(FORMAT_PREFIX)?;
+        for (i, (component, hash)) in self.components.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{component}={hash}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for HashedFingerprint {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| Error::InvalidFingerprint(reason.to_owned());
+
+        let body = s
+            .trim()
+            .strip_prefix(FORMAT_PREFIX)
+            .ok_or_else(|| invalid("unknown format version"))?;
+
+        let mut components = BTreeMap::new();
+        for entry in body.split(',').filter(|entry| !entry.is_empty()) {
+            let (name, hash) = entry
+                .split_once('=')
+                .ok_or_else(|| invalid("expected `component=hash`"))?;
+            let component = name.parse()?;
+            if hash.len() != 32 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(invalid("expected a 32 character hex hash"));
+            }
+            components.insert(component, hash.to_ascii_lowercase());
+        }
+
+        Ok(Self { components })
+    }
+}
+
+impl FromStr for Component {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Component::ALL
+            .into_iter()
+            .find(|component| component.name() == s)
+            .ok_or_else(|| Error::InvalidFingerprint(format!("unknown component `{s}`")))
+    }
+}
+
+impl Component {
+    /// The relative weight of the component in [`Fingerprint::matches`].
+    ///
+    /// The machine ID weighs the most, network adapters and disks the least
+    /// since they are the most likely to be replaced.
+    pub fn weight(&self) -> u32 {
+        match self {
+            Component::MachineId => 40,
+            Component::DmiProductUuid => 25,
+            Component::BoardSerial => 15,
+            Component::MacAddresses => 10,
+            Component::RootFsUuid => 10,
+        }
+    }
+}
+
+/// The result of [`Fingerprint::matches`].
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Match {
+    /// Weighted share of the stored components that are unchanged, from `0.0` to `1.0`.
+    pub score: f64,
+    /// Whether `score` reached the requested threshold.
+    pub accepted: bool,
+    /// Stored components with a different current value.
+    pub changed: Vec<Component>,
+    /// Stored components that are no longer available.
+    pub missing: Vec<Component>,
+}
+
+impl Fingerprint {
+    /// Hashes every component separately with `key`, see [`HashedFingerprint`].
+    ///
+    /// Like the application ID of [`crate::MachineId::app_specific`], the key keeps hashes
+    /// of different applications unrelated; unlike it, the key must be secret for the hashes
+    /// not to reveal the components.
+    pub fn hashed(&self, key: impl AsRef<[u8]>) -> HashedFingerprint {
+        HashedFingerprint {
+            components: self
+                .components()
+                .map(|(component, value)| {
+                    (component, hash_component(key.as_ref(), component, value))
+                })
+                .collect(),
+        }
+    }
+
+    /// Compares the current fingerprint with a stored one.
+    ///
+    /// Each stored component that still has the same value contributes its [`Component::weight`]
+    /// to the score, and the match is accepted if the weighted share reaches `threshold`.
+    /// Components not present in `stored` are ignored. `key` must be the one `stored` was
+    /// [`Fingerprint::hashed`] with.
+    pub fn matches(
+        &self,
+        stored: &HashedFingerprint,
+        key: impl AsRef<[u8]>,
+        threshold: f64,
+    ) -> Match {
+        let current = self.hashed(key);
+        let (mut total, mut unchanged) = (0, 0);
+        let (mut changed, mut missing) = (Vec::new(), Vec::new());
+
+        for (component, hash) in stored.components() {
+            total += component.weight();
+            match current.get(component) {
+                Some(current) if current == hash => unchanged += component.weight(),
+                Some(_) => changed.push(component),
+                None => missing.push(component),
+            }
+        }
+
+        let score = if total == 0 {
+            0.0
+        } else {
+            f64::from(unchanged) / f64::from(total)
+        };
+
+        Match {
+            score,
+            accepted: total > 0 && score >= threshold,
+            changed,
+            missing,
+        }
+    }
+}
+
+fn hash_component(key: &[u8], component: Component, value: &str) -> String {
+    let digest = app_specific::hmac_sha256(
+        key,
+        &[
+            COMPONENT_DOMAIN,
+            component.name().as_bytes(),
+            b"=",
+            value.as_bytes(),
+        ],
+    );
+    digest[..16].iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"test application secret";
+
+    fn fingerprint(values: &[(Component, &str)]) -> Fingerprint {
+        values
+            .iter()
+            .map(|(component, value)| (*component, value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_matches() {
+        let stored = fingerprint(&[
+            (Component::MachineId, "3d1219c7c4c5404aaa1f6d2a48adfda4"),
+            (Component::MacAddresses, "52:54:00:12:34:56"),
+            (
+                Component::DmiProductUuid,
+                "ec2a1b2c-3d4e-5f60-7182-93a4b5c6d7e8",
+            ),
+            (
+                Component::RootFsUuid,
+                "9b1a4c1e-0f2d-4e4b-8d57-3c1a2b3c4d5e",
+            ),
+        ])
+        .hashed(KEY);
+
+        let same = fingerprint(&[
+            (Component::MachineId, "3d1219c7c4c5404aaa1f6d2a48adfda4"),
+            (Component::MacAddresses, "52:54:00:12:34:56"),
+            (
+                Component::DmiProductUuid,
+                "ec2a1b2c-3d4e-5f60-7182-93a4b5c6d7e8",
+            ),
+            (
+                Component::RootFsUuid,
+                "9b1a4c1e-0f2d-4e4b-8d57-3c1a2b3c4d5e",
+            ),
+            (Component::BoardSerial, "new component is ignored"),
+        ]);
+        let result = same.matches(&stored, KEY, 1.0);
+        assert!(result.accepted);
+        assert_eq!(result.score, 1.0);
+
+        let new_nic = fingerprint(&[
+            (Component::MachineId, "3d1219c7c4c5404aaa1f6d2a48adfda4"),
+            (Component::MacAddresses, "52:54:00:ab:cd:ef"),
+            (
+                Component::DmiProductUuid,
+                "ec2a1b2c-3d4e-5f60-7182-93a4b5c6d7e8",
+            ),
+        ]);
+        let result = new_nic.matches(&stored, KEY, 0.7);
+        assert!(result.accepted);
+        assert_eq!(result.score, 65.0 / 85.0);
+        assert_eq!(result.changed, [Component::MacAddresses]);
+        assert_eq!(result.missing, [Component::RootFsUuid]);
+
+        let other_machine = fingerprint(&[
+            (Component::MachineId, "0123456789abcdef0123456789abcdef"),
+            (Component::MacAddresses, "52:54:00:12:34:56"),
+            (
+                Component::RootFsUuid,
+                "9b1a4c1e-0f2d-4e4b-8d57-3c1a2b3c4d5e",
+            ),
+        ]);
+        assert!(!other_machine.matches(&stored, KEY, 0.7).accepted);
+
+        // Hashes made with another application's key are unrelated.
+        let result = same.matches(&stored, b"other application secret", 0.7);
+        assert!(!result.accepted);
+        assert_eq!(result.score, 0.0);
+    }
+
+    #[test]
+    fn test_text_format() {
+        let hashed = fingerprint(&[
+            (Component::MachineId, "3d1219c7c4c5404aaa1f6d2a48adfda4"),
+            (Component::BoardSerial, "PF2ABCDE"),
+        ])
+        .hashed(KEY);
+
+        let text = hashed.to_string();
+        assert!(text.starts_with("yamid-fp2:machine_id="));
+        assert_eq!(text.parse::<HashedFingerprint>().unwrap(), hashed);
+
+        assert!("yamid-fp1:".parse::<HashedFingerprint>().is_err());
+        assert!("yamid-fp2:cpu=00".parse::<HashedFingerprint>().is_err());
+        assert!("yamid-fp2:machine_id=xyz"
+            .parse::<HashedFingerprint>()
+            .is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! Composite hardware fingerprint.
+//!
+//! Virtual machines cloned from the same template often share the machine ID.
+//! A [`Fingerprint`] mixes it with hardware identifiers such as MAC addresses and DMI serials
+//! and hashes the result into a single identifier.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use uuid::Builder;
+
+use crate::{app_specific, MachineId, Result};
+
+#[cfg(target_os = "linux")]
+mod linux;
+mod matching;
+
+pub use matching::{HashedFingerprint, Match};
+
+const FINGERPRINT_DOMAIN: &[u8] = b"yamid.fingerprint.v2";
+
+/// A single piece of information a [`Fingerprint`] is made of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum Component {
+    /// The [`MachineId`] itself.
+    MachineId,
+    /// MAC addresses of physical network interfaces, excluding randomized ones.
+    MacAddresses,
+    /// The SMBIOS system UUID.
+    DmiProductUuid,
+    /// The SMBIOS baseboard serial number.
+    BoardSerial,
+    /// The filesystem UUID of the root partition.
+    RootFsUuid,
+}
+
+impl Component {
+    pub const ALL: [Component; 5] = [
+        Component::MachineId,
+        Component::MacAddresses,
+        Component::DmiProductUuid,
+        Component::BoardSerial,
+        Component::RootFsUuid,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Component::MachineId => "machine_id",
+            Component::MacAddresses => "mac_addresses",
+            Component::DmiProductUuid => "dmi_product_uuid",
+            Component::BoardSerial => "board_serial",
+            Component::RootFsUuid => "root_fs_uuid",
+        }
+    }
+}
+
+impl std::fmt::Display for Component {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Collected component values.
+///
+/// Components that are not available on the current platform, or not readable
+/// without elevated privileges, are simply missing.
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fingerprint {
+    components: BTreeMap<Component, String>,
+}
+
+impl Fingerprint {
+    /// Collects all known components, see [`FingerprintBuilder`] to choose them.
+    pub fn new() -> Result<Self> {
+        FingerprintBuilder::new().collect()
+    }
+
+    pub fn get(&self, component: Component) -> Option<&str> {
+        self.components.get(&component).map(String::as_str)
+    }
+
+    /// Collected components and their values, in a stable order.
+    pub fn components(&self) -> impl Iterator<Item = (Component, &str)> {
+        self.components
+            .iter()
+            .map(|(component, value)| (*component, value.as_str()))
+    }
+
+    /// Hashes all collected components into a single identifier, keyed with `key`.
+    ///
+    /// As with [`Fingerprint::hashed`], the key must be secret: MAC addresses and serials
+    /// have little entropy, so without the machine ID they can be recovered from the identifier
+    /// by anyone who knows the key.
+    pub fn id(&self, key: impl AsRef<[u8]>) -> MachineId {
+        let mut parts: Vec<&[u8]> = Vec::with_capacity(self.components.len() * 4 + 1);
+        parts.push(FINGERPRINT_DOMAIN);
+        for (component, value) in &self.components {
+            parts.extend([component.name().as_bytes(), b"=", value.as_bytes(), b"\n"]);
+        }
+
+        let digest = app_specific::hmac_sha256(key.as_ref(), &parts);
+        MachineId(Builder::from_custom_bytes(app_specific::truncate(&digest)).into_uuid())
+    }
+}
+
+impl FromIterator<(Component, String)> for Fingerprint {
+    fn from_iter<T: IntoIterator<Item = (Component, String)>>(iter: T) -> Self {
+        Self {
+            components: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Chooses the components a [`Fingerprint`] is collected from.
+#[derive(Debug, Clone)]
+pub struct FingerprintBuilder {
+    components: BTreeSet<Component>,
+    root: PathBuf,
+}
+
+impl Default for FingerprintBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FingerprintBuilder {
+    /// Creates a builder with all known components.
+    pub fn new() -> Self {
+        Self {
+            components: Component::ALL.into_iter().collect(),
+            root: PathBuf::from("/"),
+        }
+    }
+
+    /// Creates a builder without any components.
+    pub fn empty() -> Self {
+        Self {
+            components: BTreeSet::new(),
+            root: PathBuf::from("/"),
+        }
+    }
+
+    pub fn component(mut self, component: Component) -> Self {
+        self.components.insert(component);
+        self
+    }
+
+    pub fn without(mut self, component: Component) -> Self {
+        self.components.remove(&component);
+        self
+    }
+
+    /// Reads file-based components from the filesystem mounted at `root`, see [`crate::MachineIdBuilder::root`].
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    /// Collects the configured components.
+    ///
+    /// Fails only if none of them is available.
+    pub fn collect(&self) -> Result<Fingerprint> {
+        let fingerprint: Fingerprint = self
+            .components
+            .iter()
+            .filter_map(|component| {
+                read_component(*component, &self.root).map(|value| (*component, value))
+            })
+            .collect();
+
+        if fingerprint.components.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no fingerprint components available",
+            )
+            .into());
+        }
+
+        Ok(fingerprint)
+    }
+}
+
+fn read_component(component: Component, root: &Path) -> Option<String> {
+    match component {
+        Component::MachineId => read_machine_id(root)
+            .ok()
+            .map(|id| id.0.simple().to_string()),
+        #[cfg(target_os = "linux")]
+        Component::MacAddresses => linux::mac_addresses(root),
+        #[cfg(target_os = "linux")]
+        Component::DmiProductUuid => linux::dmi_product_uuid(root),
+        #[cfg(target_os = "linux")]
+        Component::BoardSerial => linux::board_serial(root),
+        #[cfg(target_os = "linux")]
+        Component::RootFsUuid => linux::root_fs_uuid(root),
+        #[cfg(not(target_os = "linux"))]
+        _ => None,
+    }
+}
+
+fn read_machine_id(root: &Path) -> Result<MachineId> {
+    if root == Path::new("/") {
+        MachineId::new()
+    } else {
+        MachineId::from_root(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"test application secret";
+
+    #[test]
+    fn test_id_depends_on_every_component() {
+        let base: Fingerprint = [
+            (
+                Component::MachineId,
+                "3d1219c7c4c5404aaa1f6d2a48adfda4".to_owned(),
+            ),
+            (Component::MacAddresses, "52:54:00:12:34:56".to_owned()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(base.id(KEY), base.clone().id(KEY));
+
+        let changed: Fingerprint = [
+            (
+                Component::MachineId,
+                "3d1219c7c4c5404aaa1f6d2a48adfda4".to_owned(),
+            ),
+            (Component::MacAddresses, "52:54:00:12:34:57".to_owned()),
+        ]
+        .into_iter()
+        .collect();
+        assert_ne!(base.id(KEY), changed.id(KEY));
+
+        let partial: Fingerprint = [(
+            Component::MachineId,
+            "3d1219c7c4c5404aaa1f6d2a48adfda4".to_owned(),
+        )]
+        .into_iter()
+        .collect();
+        assert_ne!(base.id(KEY), partial.id(KEY));
+
+        assert_ne!(base.id(KEY), base.id(b"other application secret"));
+    }
+
+    #[test]
+    fn test_collect() {
+        let fingerprint = Fingerprint::new().unwrap();
+        assert!(fingerprint.get(Component::MachineId).is_some());
+
+        let without = FingerprintBuilder::new()
+            .without(Component::MachineId)
+            .collect();
+        if let Ok(without) = without {
+            assert!(without.get(Component::MachineId).is_none());
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::str::FromStr;
+
+use uuid::Uuid;
+
+use crate::{
+    encoding::{base64url_decode, base64url_encode, crockford_decode, crockford_encode},
+    error::Error,
+    MachineId,
+};
+
+/// Textual representations of a [`MachineId`], see [`MachineId::format`].
+///
+/// Hex forms are lowercase; `Display` keeps emitting uppercase hyphenated hex.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+#[non_exhaustive]
+pub enum Format {
+    /// `3d1219c7c4c5404aaa1f6d2a48adfda4`
+    Simple,
+    /// `3d1219c7-c4c5-404a-aa1f-6d2a48adfda4`, as Windows stores `MachineGuid`.
+    Hyphenated,
+    /// `{3d1219c7-c4c5-404a-aa1f-6d2a48adfda4}`
+    Braced,
+    /// `urn:uuid:3d1219c7-c4c5-404a-aa1f-6d2a48adfda4`
+    Urn,
+    /// `1X28CWFH65815AM7VD594AVZD4`, 26 Crockford base32 symbols.
+    Base32,
+    /// `PRIZx8TFQEqqH20qSK39pA`, unpadded.
+    Base64Url,
+    /// The content of `/etc/machine-id`, identical to [`Format::Simple`].
+    Systemd,
+}
+
+impl Format {
+    pub const ALL: [Format; 7] = [
+        Format::Simple,
+        Format::Hyphenated,
+        Format::Braced,
+        Format::Urn,
+        Format::Base32,
+        Format::Base64Url,
+        Format::Systemd,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Simple => "simple",
+            Format::Hyphenated => "hyphenated",
+            Format::Braced => "braced",
+            Format::Urn => "urn",
+            Format::Base32 => "base32",
+            Format::Base64Url => "base64url",
+            Format::Systemd => "systemd",
+        }
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Format::ALL
+            .into_iter()
+            .find(|format| format.name() == s)
+            .ok_or_else(|| Error::InvalidFormat(format!("unknown format `{s}`")))
+    }
+}
+
+impl MachineId {
+    /// Formats the ID as `format`.
+    pub fn format(&self, format: Format) -> String {
+        let id = &self.0;
+        match format {
+            Format::Simple | Format::Systemd => id.simple().to_string(),
+            Format::Hyphenated => id.hyphenated().to_string(),
+            Format::Braced => id.braced().to_string(),
+            Format::Urn => id.urn().to_string(),
+            Format::Base32 => crockford_encode(id.as_u128(), 26),
+            Format::Base64Url => base64url_encode(id.as_bytes()),
+        }
+    }
+}
+
+/// Parses any [`Format`], in either case for the hex and base32 forms, as well as
+/// the uppercase `Display` form.
+impl FromStr for MachineId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let uuid = match s.len() {
+            22 => base64url_decode(s)
+                .and_then(|bytes| Uuid::from_slice(&bytes).ok())
+                .ok_or_else(|| Error::InvalidFormat("invalid base64url machine ID".to_owned()))?,
+            26 => crockford_decode(s)
+                .map(Uuid::from_u128)
+                .ok_or_else(|| Error::InvalidFormat("invalid base32 machine ID".to_owned()))?,
+            _ => Uuid::parse_str(s)?,
+        };
+        Ok(MachineId(uuid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats_round_trip() {
+        let id = MachineId(Uuid::from_u128(0x3d1219c7c4c5404aaa1f6d2a48adfda4));
+        let expected = [
+            (Format::Simple, "3d1219c7c4c5404aaa1f6d2a48adfda4"),
+            (Format::Hyphenated, "3d1219c7-c4c5-404a-aa1f-6d2a48adfda4"),
+            (Format::Braced, "{3d1219c7-c4c5-404a-aa1f-6d2a48adfda4}"),
+            (Format::Urn, "urn:uuid:3d1219c7-c4c5-404a-aa1f-6d2a48adfda4"),
+            (Format::Base32, "1X28CWFH65815AM7VD594AVZD4"),
+            (Format::Base64Url, "PRIZx8TFQEqqH20qSK39pA"),
+            (Format::Systemd, "3d1219c7c4c5404aaa1f6d2a48adfda4"),
+        ];
+
+        for (format, text) in expected {
+            assert_eq!(id.format(format), text);
+            assert_eq!(text.parse::<MachineId>().unwrap(), id, "{format}");
+            assert_eq!(format.name().parse::<Format>().unwrap(), format);
+        }
+        assert_eq!(id.to_string().parse::<MachineId>().unwrap(), id);
+        assert_eq!(
+            "1x28cwfh65815am7vd594avzd4\n".parse::<MachineId>().unwrap(),
+            id
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            "ZX28CWFH65815AM7VD594AVZD4".parse::<MachineId>(),
+            Err(Error::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            "PRIZx8TFQEqqH20qSK39p+".parse::<MachineId>(),
+            Err(Error::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            "3d1219c7".parse::<MachineId>(),
+            Err(Error::InvalidUuid(_))
+        ));
+        assert!("xml".parse::<Format>().is_err());
+    }
+}
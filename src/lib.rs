@@ -1,13 +1,49 @@
+use std::path::Path;
+
 use uuid::Uuid;
 
+mod app_specific;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+mod async_builder;
+pub mod audit;
+mod boot_id;
+mod builder;
+pub mod cache;
+pub mod container;
+mod diagnose;
+pub mod dmi;
+mod encoding;
 pub mod error;
+pub mod fingerprint;
+mod format;
+#[cfg(unix)]
+pub mod provision;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+mod runtime;
+pub mod sources;
+mod support_code;
+#[cfg(test)]
+mod test_util;
+mod validation;
+pub mod virtualization;
+pub mod watch;
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use async_builder::AsyncMachineIdBuilder;
+pub use boot_id::BootId;
+pub use builder::{MachineIdBuilder, Resolved};
+pub use diagnose::{diagnose, diagnose_with, DiagnoseOptions, FileMetadata, Report, SourceReport};
+pub use format::Format;
+pub use support_code::{Correction, ParsedSupportCode, SupportCode};
+pub use validation::{Validator, KNOWN_DUPLICATES};
 
 pub type Result<T> = std::result::Result<T, error::Error>;
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MachineId(Uuid);
 
+/// Uppercase hyphenated hex, see [`MachineId::format`] for other forms.
 impl std::fmt::Display for MachineId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         std::fmt::UpperHex::fmt(&self.0, f)
@@ -29,136 +65,33 @@ impl AsRef<[u8]> for MachineId {
 }
 
 impl MachineId {
-    #[cfg(windows)]
-    pub fn new() -> Result<Self> {
-        use winreg::{enums::HKEY_LOCAL_MACHINE, RegKey};
-
-        let guid_str = RegKey::predef(HKEY_LOCAL_MACHINE)
-            .open_subkey("SOFTWARE\\Microsoft\\Cryptography")
-            .and_then(|key| key.get_value::<String, _>("MachineGuid"))?;
-
-        let machine_uuid = Uuid::parse_str(&guid_str)?;
-
-        Ok(Self(machine_uuid))
-    }
-
-    #[cfg(target_os = "linux")]
+    /// Reads the machine ID from the platform default sources, see [`MachineIdBuilder`].
+    ///
+    /// On targets without built-in sources it fails with [`error::Error::Unsupported`],
+    /// unless a source was registered with [`sources::register`].
     pub fn new() -> Result<Self> {
-        use std::fs::read_to_string;
-
-        let guid_str = read_to_string("/etc/machine-id")
-            .and_then(|data| {
-                if data.is_empty() {
-                    Err(std::io::Error::new(std::io::ErrorKind::InvalidData, ""))
-                } else {
-                    Ok(data)
-                }
-            })
-            .or_else(|_| read_to_string("/var/lib/dbus/machine-id"))?;
-        let machine_uuid = Uuid::parse_str(guid_str.trim_end())?;
-
-        Ok(Self(machine_uuid))
+        MachineIdBuilder::new().build()
     }
 
-    #[cfg(all(unix, not(target_os = "linux"), not(target_os = "macos")))]
-    pub fn new() -> Result<Self> {
-        let id = unix::host_uuid().or_else(|_| std::fs::read_to_string("/etc/hostid"))?;
-        let machine_uuid = Uuid::parse_str(id.trim_end())?;
-        Ok(Self(machine_uuid))
-    }
-
-    #[cfg(target_os = "macos")]
-    pub fn new() -> Result<Self> {
-        use apple_sys::IOKit as io;
-        use core_foundation::{
-            base::TCFType,
-            string::{CFString, CFStringRef},
-        };
-
-        struct ObjectReleaser(u32);
-        impl Drop for ObjectReleaser {
-            fn drop(&mut self) {
-                unsafe { io::IOObjectRelease(self.0) };
-            }
-        }
-
-        let uuid_str = unsafe {
-            let root = io::IORegistryEntryFromPath(
-                io::kIOMasterPortDefault,
-                "IOService:/\0".as_ptr() as _,
-            );
-
-            if root == io::MACH_PORT_NULL {
-                return Err(std::io::Error::last_os_error().into());
-            }
-
-            let root = ObjectReleaser(root);
-            let key = CFString::from_static_string("IOPlatformUUID");
-            let uuid_cref: CFStringRef = io::IORegistryEntryCreateCFProperty(
-                root.0,
-                key.as_CFTypeRef() as _,
-                io::kCFAllocatorDefault,
-                0,
-            ) as _;
-
-            if uuid_cref.is_null() {
-                return Err(std::io::Error::last_os_error().into());
-            }
-            CFString::wrap_under_create_rule(uuid_cref).to_string()
-        };
-
-        Ok(Self(uuid::Uuid::parse_str(&uuid_str)?))
-    }
-}
-
-#[cfg(all(unix, not(target_os = "linux"), not(target_os = "macos")))]
-mod unix {
-    pub fn host_uuid() -> std::io::Result<String> {
-        const KERN_HOSTUUID: i32 = 0x24i32;
-        let vec = sysctl([libc::CTL_KERN, KERN_HOSTUUID])?;
-
-        Ok(vec
-            .into_iter()
-            .take_while(|ch| *ch != 0)
-            .map(|ch| ch as char)
-            .collect::<String>())
+    /// Returns an ID scoped to the current container rather than to the host or the image,
+    /// see [`sources::ContainerId`].
+    pub fn container_scoped() -> Result<Self> {
+        MachineIdBuilder::empty()
+            .source(sources::ContainerId)
+            .build()
     }
 
-    pub fn sysctl<const N: usize>(mib: [i32; N]) -> std::io::Result<Vec<u8>> {
-        use std::ptr;
-        let (mut m, mut n) = (mib, 0);
-        let r = unsafe {
-            libc::sysctl(
-                m.as_mut_ptr() as _,
-                m.len() as _,
-                ptr::null_mut(),
-                &mut n,
-                ptr::null_mut(),
-                0,
-            )
-        };
-
-        if r != 0 {
-            return Err(std::io::Error::from_raw_os_error(r));
-        }
-        let mut b = Vec::with_capacity(n);
-        let s = unsafe {
-            let res = libc::sysctl(
-                m.as_mut_ptr() as _,
-                m.len() as _,
-                b.as_mut_ptr() as _,
-                &mut n,
-                ptr::null_mut(),
-                0,
-            );
-            b.set_len(n);
-            res
-        };
-        if s != 0 {
-            Err(std::io::Error::from_raw_os_error(s))
-        } else {
-            Ok(b)
-        }
+    /// Reads the machine ID of the Linux root filesystem mounted at `root`
+    /// (`etc/machine-id` with fallbacks to `run/machine-id` and `var/lib/dbus/machine-id`).
+    ///
+    /// Useful for mounted images, chroots and container root filesystems.
+    pub fn from_root(root: impl AsRef<Path>) -> Result<Self> {
+        MachineIdBuilder::empty()
+            .source(sources::MachineIdFile)
+            .source(sources::RunMachineIdFile)
+            .source(sources::DbusMachineIdFile)
+            .root(root.as_ref())
+            .build()
     }
 }
 
@@ -0,0 +1,388 @@
+//! Creating machine IDs the way `systemd-machine-id-setup` does, e.g. in image builders.
+//!
+//! ```no_run
+//! use yamid::provision::{Dbus, Provision};
+//!
+//! let id = Provision::new()
+//!     .root("/mnt/image")
+//!     .dbus(Dbus::Symlink)
+//!     .setup()?;
+//! # Ok::<(), yamid::error::Error>(())
+//! ```
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use uuid::Uuid;
+
+use crate::{
+    error::Error,
+    sources::{read_id_file, resolve_path, write_id_file},
+    MachineId, Result, Validator,
+};
+
+const ETC_MACHINE_ID: &str = "/etc/machine-id";
+const RUN_MACHINE_ID: &str = "/run/machine-id";
+const DBUS_MACHINE_ID: &str = "/var/lib/dbus/machine-id";
+
+/// What to do with the legacy D-Bus copy at `/var/lib/dbus/machine-id`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum Dbus {
+    /// Leave it alone.
+    #[default]
+    Keep,
+    /// Replace it with a copy of `/etc/machine-id`.
+    Copy,
+    /// Replace it with a symlink to `/etc/machine-id`, as Debian does.
+    Symlink,
+}
+
+/// A new random machine ID: a UUID v4, like systemd generates.
+pub fn generate() -> MachineId {
+    MachineId(Uuid::new_v4())
+}
+
+/// Writes `/etc/machine-id` under a root directory.
+///
+/// Files are written like systemd does, 32 lowercase hex digits and a newline with mode `0444`,
+/// to a temporary file that is then renamed into place, so readers never see partial content.
+#[derive(Debug, Clone)]
+pub struct Provision {
+    root: PathBuf,
+    dbus: Dbus,
+    id: Option<MachineId>,
+}
+
+impl Default for Provision {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Provision {
+    pub fn new() -> Self {
+        Self {
+            root: PathBuf::from("/"),
+            dbus: Dbus::Keep,
+            id: None,
+        }
+    }
+
+    /// Provisions the system mounted at `root` instead of `/`.
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    pub fn dbus(mut self, dbus: Dbus) -> Self {
+        self.dbus = dbus;
+        self
+    }
+
+    /// Writes `id` instead of reusing or generating one in [`Provision::setup`].
+    pub fn id(mut self, id: MachineId) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Makes sure `/etc/machine-id` holds a valid ID, like `systemd-machine-id-setup`.
+    ///
+    /// A valid existing ID is kept. Otherwise, e.g. for an empty or `uninitialized` file,
+    /// the ID given with [`Provision::id`] is written, or else a valid D-Bus ID is reused,
+    /// or else a new one is [`generate`]d.
+    pub fn setup(&self) -> Result<MachineId> {
+        let id = match (self.read(ETC_MACHINE_ID), self.id) {
+            (Some(existing), _) => existing,
+            (None, Some(id)) => {
+                self.write_etc(&id)?;
+                id
+            }
+            (None, None) => {
+                let id = self.read(DBUS_MACHINE_ID).unwrap_or_else(generate);
+                self.write_etc(&id)?;
+                id
+            }
+        };
+
+        self.write_dbus(&id)?;
+        Ok(id)
+    }
+
+    /// Persists the transient `/run/machine-id` systemd generated at first boot into
+    /// `/etc/machine-id`, like `systemd-machine-id-setup --commit`.
+    ///
+    /// If `/etc/machine-id` is a bind mount of the transient ID, it is unmounted first.
+    /// Without a transient ID, the ID in `/etc/machine-id` is already persistent and returned.
+    /// Nothing is written if `/etc/machine-id` already holds the transient ID, and a different
+    /// valid ID there is never overwritten.
+    pub fn commit(&self) -> Result<MachineId> {
+        let run = resolve_path(&self.root, RUN_MACHINE_ID.as_ref());
+        let etc = resolve_path(&self.root, ETC_MACHINE_ID.as_ref());
+        let id = match read_id_file(&run) {
+            Ok(id) => MachineId(id),
+            Err(Error::IoError(error)) if error.kind() == io::ErrorKind::NotFound => {
+                let id = read_id_file(&etc)?;
+                Validator::new().validate(&id)?;
+                return Ok(MachineId(id));
+            }
+            Err(error) => return Err(error),
+        };
+        Validator::new().validate(&id.0)?;
+
+        if !same_file(&etc, &run) {
+            match self.read(ETC_MACHINE_ID) {
+                Some(existing) if existing == id => return Ok(id),
+                Some(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        "/etc/machine-id holds a different machine ID",
+                    )
+                    .into())
+                }
+                None => {}
+            }
+        }
+
+        write_file(&self.root, ETC_MACHINE_ID, &id, true)?;
+        self.write_dbus(&id)?;
+        Ok(id)
+    }
+
+    /// Reads a valid ID from the given file, `None` if it is missing, uninitialized or invalid.
+    fn read(&self, path: &str) -> Option<MachineId> {
+        let id = read_id_file(&resolve_path(&self.root, path.as_ref())).ok()?;
+        Validator::new().validate(&id).ok()?;
+        Some(MachineId(id))
+    }
+
+    fn write_etc(&self, id: &MachineId) -> Result<()> {
+        write(&self.root, ETC_MACHINE_ID, id)
+    }
+
+    fn write_dbus(&self, id: &MachineId) -> Result<()> {
+        match self.dbus {
+            Dbus::Keep => Ok(()),
+            Dbus::Copy => write(&self.root, DBUS_MACHINE_ID, id),
+            Dbus::Symlink => {
+                let path = resolve_path(&self.root, DBUS_MACHINE_ID.as_ref());
+                replace(&path, false, |tmp| {
+                    std::os::unix::fs::symlink(ETC_MACHINE_ID, tmp)
+                })?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Whether both paths are the same file, e.g. a bind mount of one onto the other.
+fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+/// Atomically replaces the file at the absolute `path` under `root` with `id`.
+///
+/// Fails if `path` is a mount point, which is left mounted.
+pub(crate) fn write(root: &Path, path: &str, id: &MachineId) -> Result<()> {
+    write_file(root, path, id, false)
+}
+
+fn write_file(root: &Path, path: &str, id: &MachineId, unmount: bool) -> Result<()> {
+    let path = resolve_path(root, path.as_ref());
+    replace(&path, unmount, |tmp| write_id_file(tmp, &id.0))?;
+    Ok(())
+}
+
+/// Creates a temporary file next to `path` with `create` and renames it over `path`,
+/// unmounting `path` first if it is a mount point and `unmount` is set.
+fn replace(
+    path: &Path,
+    unmount: bool,
+    create: impl FnOnce(&Path) -> io::Result<()>,
+) -> io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = dir.join(format!(".{name}.{}.tmp", Uuid::new_v4().simple()));
+
+    let result = create(&tmp).and_then(|_| rename_over(&tmp, path, unmount));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result?;
+
+    fs::File::open(dir)?.sync_all()
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+fn rename_over(from: &Path, to: &Path, unmount: bool) -> io::Result<()> {
+    match fs::rename(from, to) {
+        // A bind mount, like systemd's transient ID over a read-only `/etc/machine-id`.
+        #[cfg(target_os = "linux")]
+        Err(error) if unmount && error.raw_os_error() == Some(libc::EBUSY) => {
+            self::unmount(to)?;
+            fs::rename(from, to)
+        }
+        result => result,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn unmount(path: &Path) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+    // SAFETY: `path` is a valid C string.
+    if unsafe { libc::umount2(path.as_ptr(), libc::UMOUNT_NOFOLLOW) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+    use crate::test_util::TempRoot;
+
+    const ID: &str = "3d1219c7c4c5404aaa1f6d2a48adfda4";
+
+    fn content(root: &TempRoot, path: &str) -> String {
+        fs::read_to_string(root.path().join(path)).unwrap()
+    }
+
+    #[test]
+    fn test_setup_generates() {
+        let root = TempRoot::new();
+        root.write("etc/machine-id", "uninitialized\n");
+
+        let id = Provision::new()
+            .root(root.path())
+            .dbus(Dbus::Copy)
+            .setup()
+            .unwrap();
+        assert_eq!(id.0.get_version_num(), 4);
+        assert_eq!(
+            content(&root, "etc/machine-id"),
+            format!("{}\n", id.0.simple())
+        );
+        assert_eq!(
+            content(&root, "var/lib/dbus/machine-id"),
+            format!("{}\n", id.0.simple())
+        );
+
+        let metadata = fs::metadata(root.path().join("etc/machine-id")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o444);
+        assert_eq!(fs::read_dir(root.path().join("etc")).unwrap().count(), 1);
+
+        assert_eq!(MachineId::from_root(root.path()).unwrap(), id);
+    }
+
+    #[test]
+    fn test_setup_keeps_or_reuses() {
+        let root = TempRoot::new();
+        root.write("etc/machine-id", format!("{ID}\n"));
+        let id = Provision::new().root(root.path()).setup().unwrap();
+        assert_eq!(id.0.simple().to_string(), ID);
+
+        let root = TempRoot::new();
+        root.write("var/lib/dbus/machine-id", format!("{ID}\n"));
+        let id = Provision::new()
+            .root(root.path())
+            .dbus(Dbus::Symlink)
+            .setup()
+            .unwrap();
+        assert_eq!(id.0.simple().to_string(), ID);
+        assert_eq!(content(&root, "etc/machine-id"), format!("{ID}\n"));
+        assert_eq!(
+            fs::read_link(root.path().join("var/lib/dbus/machine-id")).unwrap(),
+            Path::new("/etc/machine-id")
+        );
+
+        let root = TempRoot::new();
+        let given = MachineId(Uuid::from_u128(0x0f1e2d3c4b5a69788796a5b4c3d2e1f0));
+        let id = Provision::new()
+            .root(root.path())
+            .id(given)
+            .setup()
+            .unwrap();
+        assert_eq!(id, given);
+    }
+
+    #[test]
+    fn test_commit() {
+        let root = TempRoot::new();
+        root.write("etc/machine-id", "");
+        root.write("run/machine-id", format!("{ID}\n"));
+
+        let id = Provision::new().root(root.path()).commit().unwrap();
+        assert_eq!(id.0.simple().to_string(), ID);
+        assert_eq!(content(&root, "etc/machine-id"), format!("{ID}\n"));
+
+        fs::remove_file(root.path().join("run/machine-id")).unwrap();
+        assert_eq!(Provision::new().root(root.path()).commit().unwrap(), id);
+
+        // Without a transient ID, the persistent one is validated as well.
+        let root = TempRoot::new();
+        root.write("etc/machine-id", "00000000000000000000000000000000\n");
+        assert!(matches!(
+            Provision::new().root(root.path()).commit(),
+            Err(Error::SuspiciousId { .. })
+        ));
+
+        // Well-formed, but rejected by the validator.
+        let root = TempRoot::new();
+        root.write("run/machine-id", "00000000000000000000000000000000\n");
+        assert!(matches!(
+            Provision::new().root(root.path()).commit(),
+            Err(Error::SuspiciousId { .. })
+        ));
+    }
+
+    #[test]
+    fn test_commit_existing() {
+        let mode = |root: &TempRoot| {
+            let metadata = fs::metadata(root.path().join("etc/machine-id")).unwrap();
+            metadata.permissions().mode() & 0o777
+        };
+
+        // Already committed: nothing is written.
+        let root = TempRoot::new();
+        root.write("etc/machine-id", format!("{ID}\n"));
+        root.write("run/machine-id", format!("{ID}\n"));
+        let before = mode(&root);
+        let id = Provision::new().root(root.path()).commit().unwrap();
+        assert_eq!(id.0.simple().to_string(), ID);
+        assert_eq!(mode(&root), before);
+
+        // A different persistent ID is kept.
+        let other = "0f1e2d3c4b5a69788796a5b4c3d2e1f0";
+        let root = TempRoot::new();
+        root.write("etc/machine-id", format!("{other}\n"));
+        root.write("run/machine-id", format!("{ID}\n"));
+        assert!(Provision::new().root(root.path()).commit().is_err());
+        assert_eq!(content(&root, "etc/machine-id"), format!("{other}\n"));
+
+        // `/etc/machine-id` is the transient file itself, as with systemd's bind mount.
+        let root = TempRoot::new();
+        root.write("run/machine-id", format!("{ID}\n"));
+        fs::create_dir_all(root.path().join("etc")).unwrap();
+        fs::hard_link(
+            root.path().join("run/machine-id"),
+            root.path().join("etc/machine-id"),
+        )
+        .unwrap();
+        Provision::new().root(root.path()).commit().unwrap();
+        assert_eq!(content(&root, "etc/machine-id"), format!("{ID}\n"));
+        assert_eq!(mode(&root), 0o444);
+    }
+}
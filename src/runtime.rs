@@ -0,0 +1,66 @@
+//! The few runtime facilities the async API needs, from whichever runtime is enabled.
+
+use std::{future::Future, io, time::Duration};
+
+enum Runtime {
+    #[cfg(feature = "tokio")]
+    Tokio,
+    #[cfg(feature = "async-std")]
+    AsyncStd,
+}
+
+/// With both features enabled, tokio is used inside a tokio runtime and async-std elsewhere.
+///
+/// With only tokio, there is nothing to run on outside a tokio runtime, where tokio would panic.
+fn current() -> io::Result<Runtime> {
+    #[cfg(all(feature = "tokio", feature = "async-std"))]
+    return Ok(match tokio::runtime::Handle::try_current() {
+        Ok(_) => Runtime::Tokio,
+        Err(_) => Runtime::AsyncStd,
+    });
+
+    #[cfg(all(feature = "tokio", not(feature = "async-std")))]
+    return match tokio::runtime::Handle::try_current() {
+        Ok(_) => Ok(Runtime::Tokio),
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "not running within a tokio runtime",
+        )),
+    };
+
+    #[cfg(all(feature = "async-std", not(feature = "tokio")))]
+    return Ok(Runtime::AsyncStd);
+}
+
+/// Runs blocking code on the runtime's blocking thread pool.
+pub(crate) async fn spawn_blocking<T, F>(f: F) -> io::Result<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    Ok(match current()? {
+        #[cfg(feature = "tokio")]
+        Runtime::Tokio => match tokio::task::spawn_blocking(f).await {
+            Ok(value) => value,
+            Err(error) => std::panic::resume_unwind(error.into_panic()),
+        },
+        #[cfg(feature = "async-std")]
+        Runtime::AsyncStd => async_std::task::spawn_blocking(f).await,
+    })
+}
+
+/// Awaits `future` for at most `duration`, `None` if it took longer.
+///
+/// tokio panics if its runtime was built without the time driver, which cannot be
+/// detected beforehand: the requirement is documented on the builder instead.
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> io::Result<Option<F::Output>> {
+    Ok(match current()? {
+        #[cfg(feature = "tokio")]
+        Runtime::Tokio => tokio::time::timeout(duration, future).await.ok(),
+        #[cfg(feature = "async-std")]
+        Runtime::AsyncStd => async_std::future::timeout(duration, future).await.ok(),
+    })
+}
@@ -0,0 +1,79 @@
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+};
+
+use uuid::Uuid;
+
+use super::IdSource;
+use crate::{error::Location, Result};
+
+/// The future returned by [`AsyncIdSource::read`].
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A place a machine ID can be read from without blocking the async runtime,
+/// e.g. over the network or IPC.
+///
+/// Any [`IdSource`] can be used as an async one with [`Blocking`].
+pub trait AsyncIdSource: Send + Sync {
+    /// Short name reported when this source provides the ID.
+    fn name(&self) -> &str;
+
+    /// Reads and parses the ID, see [`IdSource::read`].
+    fn read<'a>(&'a self, root: &'a Path) -> BoxFuture<'a, Result<Uuid>>;
+
+    /// Where the source looks for the ID, reported in errors and diagnostics.
+    fn location(&self, _root: &Path) -> Option<Location> {
+        None
+    }
+}
+
+impl<S: AsyncIdSource + ?Sized> AsyncIdSource for Box<S> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn read<'a>(&'a self, root: &'a Path) -> BoxFuture<'a, Result<Uuid>> {
+        (**self).read(root)
+    }
+
+    fn location(&self, root: &Path) -> Option<Location> {
+        (**self).location(root)
+    }
+}
+
+/// Runs a blocking [`IdSource`] on the runtime's blocking thread pool.
+///
+/// A read that times out keeps running in the background until it returns,
+/// as blocking calls cannot be cancelled.
+pub struct Blocking(Arc<dyn IdSource>);
+
+impl Blocking {
+    pub fn new(source: impl IdSource + 'static) -> Self {
+        Self(Arc::new(source))
+    }
+}
+
+impl From<Box<dyn IdSource>> for Blocking {
+    fn from(source: Box<dyn IdSource>) -> Self {
+        Self(Arc::from(source))
+    }
+}
+
+impl AsyncIdSource for Blocking {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn read<'a>(&'a self, root: &'a Path) -> BoxFuture<'a, Result<Uuid>> {
+        let source = self.0.clone();
+        let root = PathBuf::from(root);
+        Box::pin(async move { crate::runtime::spawn_blocking(move || source.read(&root)).await? })
+    }
+
+    fn location(&self, root: &Path) -> Option<Location> {
+        self.0.location(root)
+    }
+}
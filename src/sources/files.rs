@@ -0,0 +1,70 @@
+use std::path::Path;
+
+use uuid::Uuid;
+
+use super::{read_id_file, resolve_path, IdSource};
+use crate::{error::Location, Result};
+
+/// `/etc/machine-id`, see [machine-id(5)](https://man7.org/linux/man-pages/man5/machine-id.5.html).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MachineIdFile;
+
+impl IdSource for MachineIdFile {
+    fn name(&self) -> &str {
+        "machine-id"
+    }
+
+    fn read(&self, root: &Path) -> Result<Uuid> {
+        read_id_file(&resolve_path(root, "/etc/machine-id".as_ref()))
+    }
+
+    fn location(&self, root: &Path) -> Option<Location> {
+        Some(Location::File(resolve_path(
+            root,
+            "/etc/machine-id".as_ref(),
+        )))
+    }
+}
+
+/// `/run/machine-id`, the transient ID systemd generates at first boot
+/// while `/etc/machine-id` is still read-only or `uninitialized`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunMachineIdFile;
+
+impl IdSource for RunMachineIdFile {
+    fn name(&self) -> &str {
+        "run"
+    }
+
+    fn read(&self, root: &Path) -> Result<Uuid> {
+        read_id_file(&resolve_path(root, "/run/machine-id".as_ref()))
+    }
+
+    fn location(&self, root: &Path) -> Option<Location> {
+        Some(Location::File(resolve_path(
+            root,
+            "/run/machine-id".as_ref(),
+        )))
+    }
+}
+
+/// `/var/lib/dbus/machine-id`, the legacy D-Bus copy of the machine ID.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DbusMachineIdFile;
+
+impl IdSource for DbusMachineIdFile {
+    fn name(&self) -> &str {
+        "dbus"
+    }
+
+    fn read(&self, root: &Path) -> Result<Uuid> {
+        read_id_file(&resolve_path(root, "/var/lib/dbus/machine-id".as_ref()))
+    }
+
+    fn location(&self, root: &Path) -> Option<Location> {
+        Some(Location::File(resolve_path(
+            root,
+            "/var/lib/dbus/machine-id".as_ref(),
+        )))
+    }
+}
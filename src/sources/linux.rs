@@ -0,0 +1,31 @@
+use std::path::Path;
+
+use uuid::Uuid;
+
+use super::{resolve_path, IdSource};
+use crate::{dmi::Dmi, error::Location, Result};
+
+/// The SMBIOS system UUID, see [`Dmi`].
+///
+/// Survives OS reinstalls, but is usually readable by root only.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DmiProductUuid;
+
+impl IdSource for DmiProductUuid {
+    fn name(&self) -> &str {
+        "dmi"
+    }
+
+    fn read(&self, root: &Path) -> Result<Uuid> {
+        Dmi::from_root(root)?.product_uuid.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "no valid DMI product UUID").into()
+        })
+    }
+
+    fn location(&self, root: &Path) -> Option<Location> {
+        Some(Location::File(resolve_path(
+            root,
+            "/sys/class/dmi/id/product_uuid".as_ref(),
+        )))
+    }
+}
@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use uuid::Uuid;
+
+use super::{ensure_host_root, parse_id, IdSource};
+use crate::{error::Location, Result};
+
+/// The `IOPlatformUUID` property of the IOKit registry root.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IoPlatformUuid;
+
+impl IdSource for IoPlatformUuid {
+    fn name(&self) -> &str {
+        "io-platform-uuid"
+    }
+
+    fn read(&self, root: &Path) -> Result<Uuid> {
+        use apple_sys::IOKit as io;
+        use core_foundation::{
+            base::TCFType,
+            string::{CFString, CFStringRef},
+        };
+
+        ensure_host_root(root)?;
+
+        struct ObjectReleaser(u32);
+        impl Drop for ObjectReleaser {
+            fn drop(&mut self) {
+                unsafe { io::IOObjectRelease(self.0) };
+            }
+        }
+
+        let uuid_str = unsafe {
+            let root = io::IORegistryEntryFromPath(
+                io::kIOMasterPortDefault,
+                "IOService:/\0".as_ptr() as _,
+            );
+
+            if root == io::MACH_PORT_NULL {
+                return Err(std::io::Error::last_os_error().into());
+            }
+
+            let root = ObjectReleaser(root);
+            let key = CFString::from_static_string("IOPlatformUUID");
+            let uuid_cref: CFStringRef = io::IORegistryEntryCreateCFProperty(
+                root.0,
+                key.as_CFTypeRef() as _,
+                io::kCFAllocatorDefault,
+                0,
+            ) as _;
+
+            if uuid_cref.is_null() {
+                return Err(std::io::Error::last_os_error().into());
+            }
+            CFString::wrap_under_create_rule(uuid_cref).to_string()
+        };
+
+        parse_id(&uuid_str)
+    }
+
+    fn location(&self, _root: &Path) -> Option<Location> {
+        Some(Location::IoRegistry(
+            "IOService:/ IOPlatformUUID".to_owned(),
+        ))
+    }
+}
@@ -0,0 +1,368 @@
+//! Built-in machine ID sources and the [`IdSource`] trait to plug in custom ones.
+
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use uuid::Uuid;
+
+use crate::{
+    error::{Error, Location, RawContent},
+    Result,
+};
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+mod asynchronous;
+mod files;
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "macos")]
+mod macos;
+mod overrides;
+#[cfg(any(windows, unix))]
+mod persisted;
+#[cfg(all(unix, not(target_os = "linux"), not(target_os = "macos")))]
+mod unix;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use asynchronous::{AsyncIdSource, Blocking, BoxFuture};
+pub use files::{DbusMachineIdFile, MachineIdFile, RunMachineIdFile};
+#[cfg(target_os = "linux")]
+pub use linux::DmiProductUuid;
+#[cfg(target_os = "macos")]
+pub use macos::IoPlatformUuid;
+pub use overrides::{Override, OVERRIDE_FILE_VAR, OVERRIDE_VAR};
+#[cfg(any(windows, unix))]
+pub use persisted::Persisted;
+#[cfg(all(unix, not(target_os = "linux"), not(target_os = "macos")))]
+pub use unix::{HostIdFile, KernHostUuid};
+#[cfg(windows)]
+pub use windows::MachineGuid;
+
+/// A single place a machine ID can be read from.
+pub trait IdSource: Send + Sync {
+    /// Short name reported when this source provides the ID.
+    fn name(&self) -> &str;
+
+    /// Reads and parses the ID.
+    ///
+    /// File-based sources resolve their absolute paths against `root`, which is `/`
+    /// unless the builder was configured with [`crate::MachineIdBuilder::root`].
+    fn read(&self, root: &Path) -> Result<Uuid>;
+
+    /// Where the source looks for the ID, reported in errors and diagnostics.
+    fn location(&self, _root: &Path) -> Option<Location> {
+        None
+    }
+}
+
+impl<S: IdSource + ?Sized> IdSource for Arc<S> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn read(&self, root: &Path) -> Result<Uuid> {
+        (**self).read(root)
+    }
+
+    fn location(&self, root: &Path) -> Option<Location> {
+        (**self).location(root)
+    }
+}
+
+impl<S: IdSource + ?Sized> IdSource for Box<S> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn read(&self, root: &Path) -> Result<Uuid> {
+        (**self).read(root)
+    }
+
+    fn location(&self, root: &Path) -> Option<Location> {
+        (**self).location(root)
+    }
+}
+
+/// Reads the ID from an arbitrary text file, resolved against the configured root.
+#[derive(Debug, Clone)]
+pub struct File {
+    name: String,
+    path: PathBuf,
+}
+
+impl File {
+    pub fn new(name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl IdSource for File {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn read(&self, root: &Path) -> Result<Uuid> {
+        read_id_file(&resolve_path(root, &self.path))
+    }
+
+    fn location(&self, root: &Path) -> Option<Location> {
+        Some(Location::File(resolve_path(root, &self.path)))
+    }
+}
+
+/// The ID of the container the process runs in, see [`crate::container::container_id`].
+///
+/// Container IDs are 256-bit random values, the first 128 bits of which are used as the UUID.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ContainerId;
+
+impl IdSource for ContainerId {
+    fn name(&self) -> &str {
+        "container"
+    }
+
+    fn read(&self, root: &Path) -> Result<Uuid> {
+        let id = crate::container::container_id_in(root).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "no container ID found")
+        })?;
+        parse_id(&id[..32])
+    }
+
+    fn location(&self, root: &Path) -> Option<Location> {
+        Some(Location::File(resolve_path(
+            root,
+            "/proc/self/cgroup".as_ref(),
+        )))
+    }
+}
+
+/// Reads the ID from an environment variable.
+#[derive(Debug, Clone)]
+pub struct Env {
+    name: String,
+    var: String,
+}
+
+impl Env {
+    pub fn new(var: impl Into<String>) -> Self {
+        let var = var.into();
+        Self {
+            name: format!("env:{var}"),
+            var,
+        }
+    }
+}
+
+impl IdSource for Env {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn read(&self, _root: &Path) -> Result<Uuid> {
+        let value = std::env::var(&self.var)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::NotFound, e))?;
+        parse_id(&value)
+    }
+
+    fn location(&self, _root: &Path) -> Option<Location> {
+        Some(Location::EnvVar(self.var.clone()))
+    }
+}
+
+/// A source backed by a closure, see [`from_fn`].
+pub struct FnSource<F> {
+    name: String,
+    f: F,
+}
+
+/// Creates a custom source from a closure.
+pub fn from_fn<F>(name: impl Into<String>, f: F) -> FnSource<F>
+where
+    F: Fn() -> Result<Uuid> + Send + Sync,
+{
+    FnSource {
+        name: name.into(),
+        f,
+    }
+}
+
+impl<F> IdSource for FnSource<F>
+where
+    F: Fn() -> Result<Uuid> + Send + Sync,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn read(&self, _root: &Path) -> Result<Uuid> {
+        (self.f)()
+    }
+}
+
+/// Whether the current platform has built-in sources.
+pub(crate) const PLATFORM_SUPPORTED: bool = cfg!(any(windows, unix));
+
+/// Sources appended to the platform defaults, see [`register`].
+pub(crate) type Registry = RwLock<Vec<Arc<dyn IdSource>>>;
+
+static REGISTERED: Registry = RwLock::new(Vec::new());
+
+/// Registers a source for the whole process.
+///
+/// `MachineId::new` and [`crate::MachineIdBuilder::new`] try registered sources after
+/// the platform defaults, in registration order. This is how targets without built-in
+/// sources (wasm32, UEFI, ...) can still provide a machine ID.
+pub fn register(source: impl IdSource + 'static) {
+    register_in(&REGISTERED, source);
+}
+
+pub(crate) fn register_in(registry: &Registry, source: impl IdSource + 'static) {
+    registry
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(Arc::new(source));
+}
+
+/// The sources `MachineId::new` tries on the current platform, in order,
+/// followed by the [`register`]ed ones.
+pub fn default_sources() -> Vec<Box<dyn IdSource>> {
+    default_sources_with(&REGISTERED)
+}
+
+/// The platform sources followed by the ones in `registry`.
+pub(crate) fn default_sources_with(registry: &Registry) -> Vec<Box<dyn IdSource>> {
+    let mut sources = platform_sources();
+    sources.extend(
+        registry
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|source| Box::new(source.clone()) as Box<dyn IdSource>),
+    );
+    sources
+}
+
+fn platform_sources() -> Vec<Box<dyn IdSource>> {
+    #[cfg(windows)]
+    return vec![Box::new(MachineGuid)];
+
+    #[cfg(target_os = "linux")]
+    return vec![
+        Box::new(MachineIdFile),
+        Box::new(RunMachineIdFile),
+        Box::new(DbusMachineIdFile),
+    ];
+
+    #[cfg(target_os = "macos")]
+    return vec![Box::new(IoPlatformUuid)];
+
+    #[cfg(all(unix, not(target_os = "linux"), not(target_os = "macos")))]
+    return vec![Box::new(KernHostUuid), Box::new(HostIdFile)];
+
+    #[cfg(not(any(windows, unix)))]
+    return Vec::new();
+}
+
+/// Maps an absolute `path` into the filesystem mounted at `root`.
+///
+/// With the host root `/`, an absolute `path` is returned as is, so a Windows path
+/// keeps its drive instead of resolving on the current one.
+pub(crate) fn resolve_path(root: &Path, path: &Path) -> PathBuf {
+    if root == Path::new("/") && path.is_absolute() {
+        return path.to_owned();
+    }
+    let relative: PathBuf = path
+        .components()
+        .filter(|component| !matches!(component, Component::Prefix(_) | Component::RootDir))
+        .collect();
+    root.join(relative)
+}
+
+/// Fails for sources that query the running system and thus cannot honour an alternative root.
+#[cfg(any(windows, all(unix, not(target_os = "linux"))))]
+pub(crate) fn ensure_host_root(root: &Path) -> Result<()> {
+    if root == Path::new("/") {
+        Ok(())
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "source cannot be read from an alternative root",
+        )
+        .into())
+    }
+}
+
+/// Reads an ID file, reporting an empty file or systemd's first boot marker as [`Error::Uninitialized`].
+pub(crate) fn read_id_file(path: &Path) -> Result<Uuid> {
+    let data = std::fs::read_to_string(path)?;
+    let trimmed = data.trim();
+    if trimmed.is_empty() || trimmed == "uninitialized" {
+        return Err(Error::Uninitialized {
+            path: path.to_owned(),
+        });
+    }
+    parse_id(&data)
+}
+
+/// Writes `id` the way systemd does, 32 lowercase hex digits and a newline, into a new file
+/// with mode `0444`, synced to disk.
+#[cfg(any(windows, unix))]
+pub(crate) fn write_id_file(path: &Path, id: &Uuid) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o444);
+
+    let mut file = options.open(path)?;
+    writeln!(file, "{}", id.simple())?;
+    file.sync_all()
+}
+
+pub(crate) fn parse_id(data: &str) -> Result<Uuid> {
+    Uuid::parse_str(data.trim_end()).map_err(|error| Error::InvalidContent {
+        raw: RawContent::new(data),
+        error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_path() {
+        let root = Path::new("/mnt/image");
+        assert_eq!(
+            resolve_path(root, "/etc/machine-id".as_ref()),
+            Path::new("/mnt/image/etc/machine-id")
+        );
+        assert_eq!(
+            resolve_path("/".as_ref(), "/etc/machine-id".as_ref()),
+            Path::new("/etc/machine-id")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_resolve_path_windows() {
+        let path = Path::new(r"D:\Users\x\AppData\Local\app\machine-id");
+        assert_eq!(resolve_path("/".as_ref(), path), path);
+        assert_eq!(
+            resolve_path(r"C:\mnt\image".as_ref(), path),
+            Path::new(r"C:\mnt\image\Users\x\AppData\Local\app\machine-id")
+        );
+    }
+}
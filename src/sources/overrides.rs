@@ -0,0 +1,128 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use uuid::Uuid;
+
+use super::{parse_id, read_id_file, IdSource};
+use crate::{error::Location, Result};
+
+/// Environment variable with the machine ID to use instead of the OS-provided one.
+pub const OVERRIDE_VAR: &str = "YAMID_MACHINE_ID";
+
+/// Environment variable with the path of a file containing the machine ID to use instead.
+pub const OVERRIDE_FILE_VAR: &str = "YAMID_MACHINE_ID_FILE";
+
+/// A forced machine ID for tests, CI runners and air-gapped deployments.
+///
+/// Reads [`OVERRIDE_VAR`], then the file named by [`OVERRIDE_FILE_VAR`].
+/// [`crate::MachineIdBuilder::new`] consults it before any other source, so it applies
+/// to `MachineId::new` as well. The override is validated like any other ID and reported
+/// as the `override` source.
+///
+/// Overrides can be refused at runtime with [`crate::MachineIdBuilder::allow_override`],
+/// or for the whole build with `RUSTFLAGS="--cfg yamid_no_override"`.
+#[derive(Debug, Clone)]
+pub struct Override {
+    var: String,
+    file_var: String,
+}
+
+impl Default for Override {
+    fn default() -> Self {
+        Self::from_vars(OVERRIDE_VAR, OVERRIDE_FILE_VAR)
+    }
+}
+
+impl Override {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the override from custom environment variables.
+    pub fn from_vars(var: impl Into<String>, file_var: impl Into<String>) -> Self {
+        Self {
+            var: var.into(),
+            file_var: file_var.into(),
+        }
+    }
+
+    /// Whether either of the variables is set. Empty variables count as unset.
+    pub fn is_set(&self) -> bool {
+        self.value().is_some() || self.file().is_some()
+    }
+
+    fn value(&self) -> Option<OsString> {
+        std::env::var_os(&self.var).filter(|value| !value.is_empty())
+    }
+
+    fn file(&self) -> Option<PathBuf> {
+        std::env::var_os(&self.file_var)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+    }
+}
+
+impl IdSource for Override {
+    fn name(&self) -> &str {
+        "override"
+    }
+
+    fn read(&self, _root: &Path) -> Result<Uuid> {
+        // The override file is a process setting, so it is not resolved against `root`.
+        if let Some(value) = self.value() {
+            let value = value.into_string().map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, "override is not UTF-8")
+            })?;
+            return parse_id(&value);
+        }
+
+        match self.file() {
+            Some(path) => read_id_file(&path),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no machine ID override set",
+            )
+            .into()),
+        }
+    }
+
+    fn location(&self, _root: &Path) -> Option<Location> {
+        match self.file() {
+            Some(path) if self.value().is_none() => Some(Location::File(path)),
+            _ => Some(Location::EnvVar(self.var.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempRoot;
+
+    #[test]
+    fn test_empty_value_is_unset() {
+        let source = Override::from_vars("YAMID_TEST_EMPTY", "YAMID_TEST_EMPTY_UNUSED_FILE");
+        std::env::set_var("YAMID_TEST_EMPTY", "");
+        assert!(!source.is_set());
+
+        std::env::set_var("YAMID_TEST_EMPTY", "0f1e2d3c4b5a69788796a5b4c3d2e1f0");
+        assert!(source.is_set());
+        std::env::remove_var("YAMID_TEST_EMPTY");
+    }
+
+    #[test]
+    fn test_empty_file_is_unset() {
+        let root = TempRoot::new();
+        let file = root.write("override", "0f1e2d3c4b5a69788796a5b4c3d2e1f0\n");
+        let source = Override::from_vars("YAMID_TEST_EMPTY_FILE_UNUSED", "YAMID_TEST_EMPTY_FILE");
+        std::env::set_var("YAMID_TEST_EMPTY_FILE", "");
+        assert!(!source.is_set());
+
+        std::env::set_var("YAMID_TEST_EMPTY_FILE", &file);
+        assert!(source.is_set());
+        assert_eq!(source.location(Path::new("/")), Some(Location::File(file)));
+        std::env::remove_var("YAMID_TEST_EMPTY_FILE");
+    }
+}
@@ -0,0 +1,343 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use uuid::Uuid;
+
+use super::{read_id_file, resolve_path, write_id_file, IdSource};
+use crate::{
+    error::{Error, Location},
+    Result,
+};
+
+const FILE_NAME: &str = "machine-id";
+
+#[derive(Debug, Clone)]
+enum Place {
+    Path(PathBuf),
+    StateDir(String),
+    AppData(String),
+}
+
+/// A random ID generated on first use and stored in a file, for systems without any OS-provided ID.
+///
+/// The file is created atomically with mode `0444`, so concurrent first calls
+/// from several processes all end up with the same ID. On filesystems without hard links,
+/// a file left empty by a process that died while creating it is replaced.
+///
+/// Not part of the default sources, add it explicitly as the last resort:
+/// ```no_run
+/// use yamid::{sources, MachineIdBuilder};
+///
+/// let id = MachineIdBuilder::new()
+///     .source(sources::Persisted::state_dir("my-app"))
+///     .build()?;
+/// # Ok::<(), yamid::error::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Persisted {
+    place: Place,
+}
+
+impl Persisted {
+    /// Stores the ID at the given path.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            place: Place::Path(path.into()),
+        }
+    }
+
+    /// Stores the ID in `<app>/machine-id` under the per-user state directory:
+    /// `$XDG_STATE_HOME` or `~/.local/state` on Unix, and the same as [`Persisted::app_data`] elsewhere.
+    pub fn state_dir(app: impl Into<String>) -> Self {
+        Self {
+            place: Place::StateDir(app.into()),
+        }
+    }
+
+    /// Stores the ID in `<app>/machine-id` under the per-user application data directory:
+    /// `%LOCALAPPDATA%` on Windows, `~/Library/Application Support` on macOS
+    /// and `$XDG_DATA_HOME` or `~/.local/share` elsewhere.
+    pub fn app_data(app: impl Into<String>) -> Self {
+        Self {
+            place: Place::AppData(app.into()),
+        }
+    }
+
+    /// The file the ID is stored in, if the location can be determined.
+    pub fn path(&self) -> Option<PathBuf> {
+        match &self.place {
+            Place::Path(path) => Some(path.clone()),
+            Place::StateDir(app) => state_dir().map(|dir| dir.join(app).join(FILE_NAME)),
+            Place::AppData(app) => app_data_dir().map(|dir| dir.join(app).join(FILE_NAME)),
+        }
+    }
+}
+
+impl IdSource for Persisted {
+    fn name(&self) -> &str {
+        "persisted"
+    }
+
+    fn read(&self, root: &Path) -> Result<Uuid> {
+        let path = self.path().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "cannot determine the persisted ID location",
+            )
+        })?;
+        read_or_persist(&resolve_path(root, &path), |from, to| {
+            fs::hard_link(from, to)
+        })
+    }
+
+    fn location(&self, root: &Path) -> Option<Location> {
+        self.path()
+            .map(|path| Location::File(resolve_path(root, &path)))
+    }
+}
+
+/// Reads the ID from `path`, creating it first if it does not exist.
+///
+/// An empty or partial file may still be being written by another process, see
+/// [`read_persisted`]. One that stays empty was left by a writer that died, and is replaced.
+fn read_or_persist(
+    path: &Path,
+    link: impl Fn(&Path, &Path) -> std::io::Result<()>,
+) -> Result<Uuid> {
+    match read_id_file(path) {
+        Err(Error::IoError(error)) if error.kind() == std::io::ErrorKind::NotFound => {
+            persist_new(path, &link)?;
+        }
+        Err(Error::Uninitialized { .. } | Error::InvalidContent { .. }) => {}
+        result => return result,
+    }
+
+    match read_persisted(path) {
+        Err(Error::Uninitialized { .. }) if is_empty(path) => {
+            replace_empty(path, &link)?;
+            read_persisted(path)
+        }
+        result => result,
+    }
+}
+
+fn is_empty(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.len() == 0)
+}
+
+/// Replaces the empty file at `path` with a new ID, if it is still empty.
+///
+/// The empty file is first moved aside, which only one process can do. If another process
+/// replaced it in the meantime, what was moved aside is its new ID, which is put back.
+fn replace_empty(
+    path: &Path,
+    link: impl Fn(&Path, &Path) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let claimed = dir.join(format!(".{FILE_NAME}.{}.empty", Uuid::new_v4().simple()));
+    match fs::rename(path, &claimed) {
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        result => result?,
+    }
+
+    if fs::metadata(&claimed)?.len() != 0 {
+        return fs::rename(&claimed, path);
+    }
+    let _ = fs::remove_file(&claimed);
+    persist_new(path, link)
+}
+
+/// Writes a new random ID to `path` unless it already exists.
+///
+/// The ID is written to a temporary file first and then hard-linked to `path`, which fails
+/// if another process got there first, so the file never has partial or conflicting content.
+/// On filesystems without hard links the file is created exclusively instead, see [`read_persisted`].
+fn persist_new(
+    path: &Path,
+    link: impl Fn(&Path, &Path) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+
+    let id = Uuid::new_v4();
+    let tmp = dir.join(format!(".{FILE_NAME}.{}.tmp", id.simple()));
+
+    let result = write_id_file(&tmp, &id).and_then(|_| match link(&tmp, path) {
+        Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => Ok(()),
+        Err(_) => match write_id_file(path, &id) {
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => Ok(()),
+            result => result,
+        },
+        result => result,
+    });
+
+    let _ = fs::remove_file(&tmp);
+    result
+}
+
+/// Reads a file another process may still be writing without hard links, waiting
+/// briefly while it is empty or partial.
+fn read_persisted(path: &Path) -> Result<Uuid> {
+    const ATTEMPTS: u32 = 50;
+
+    let mut attempt = 0;
+    loop {
+        match read_id_file(path) {
+            Err(Error::Uninitialized { .. } | Error::InvalidContent { .. })
+                if attempt < ATTEMPTS =>
+            {
+                attempt += 1;
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            result => return result,
+        }
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let home = std::env::var_os("USERPROFILE");
+    #[cfg(not(windows))]
+    let home = std::env::var_os("HOME");
+
+    home.filter(|home| !home.is_empty()).map(PathBuf::from)
+}
+
+fn env_dir(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+}
+
+fn state_dir() -> Option<PathBuf> {
+    if cfg!(all(unix, not(target_os = "macos"))) {
+        env_dir("XDG_STATE_HOME").or_else(|| home_dir().map(|home| home.join(".local/state")))
+    } else {
+        app_data_dir()
+    }
+}
+
+fn app_data_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        env_dir("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        home_dir().map(|home| home.join("Library/Application Support"))
+    } else {
+        env_dir("XDG_DATA_HOME").or_else(|| home_dir().map(|home| home.join(".local/share")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempRoot;
+
+    #[test]
+    fn test_generated_once() {
+        let root = TempRoot::new();
+        let source = Persisted::new(root.path().join("state/app/machine-id"));
+
+        let first = source.read("/".as_ref()).unwrap();
+        let second = source.read("/".as_ref()).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first.get_version_num(), 4);
+
+        let content = fs::read_to_string(root.path().join("state/app/machine-id")).unwrap();
+        assert_eq!(content, format!("{}\n", first.simple()));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = fs::metadata(root.path().join("state/app/machine-id")).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o444);
+        }
+
+        let leftovers = fs::read_dir(root.path().join("state/app")).unwrap().count();
+        assert_eq!(leftovers, 1);
+    }
+
+    #[test]
+    fn test_concurrent_first_calls() {
+        let root = TempRoot::new();
+        let source = Persisted::new("/var/lib/app/machine-id");
+
+        let ids: Vec<Uuid> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| source.read(root.path()).unwrap()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        assert!(ids.iter().all(|id| *id == ids[0]));
+    }
+
+    #[test]
+    fn test_concurrent_first_calls_without_hard_links() {
+        let root = TempRoot::new();
+        let path = root.path().join("var/lib/app/machine-id");
+        let no_hard_links =
+            |_: &Path, _: &Path| Err(std::io::Error::from(std::io::ErrorKind::Unsupported));
+
+        let ids: Vec<Uuid> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| read_or_persist(&path, no_hard_links).unwrap()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        assert!(ids.iter().all(|id| *id == ids[0]));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{}\n", ids[0].simple())
+        );
+        let leftovers = fs::read_dir(path.parent().unwrap()).unwrap().count();
+        assert_eq!(leftovers, 1);
+    }
+
+    #[test]
+    fn test_reader_after_empty_file() {
+        let root = TempRoot::new();
+        let path = root.write("var/lib/app/machine-id", "");
+        let no_hard_links =
+            |_: &Path, _: &Path| Err(std::io::Error::from(std::io::ErrorKind::Unsupported));
+
+        // The writer that created the file is still writing it.
+        let written = Uuid::from_u128(0x3d1219c7c4c5404aaa1f6d2a48adfda4);
+        let id = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                fs::write(&path, format!("{}\n", written.simple())).unwrap();
+            });
+            read_or_persist(&path, no_hard_links).unwrap()
+        });
+        assert_eq!(id, written);
+
+        // The writer died: the file is replaced.
+        fs::remove_file(&path).unwrap();
+        fs::write(&path, "").unwrap();
+        let id = read_or_persist(&path, no_hard_links).unwrap();
+        assert_eq!(id.get_version_num(), 4);
+        assert_eq!(read_or_persist(&path, no_hard_links).unwrap(), id);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{}\n", id.simple())
+        );
+        let leftovers = fs::read_dir(path.parent().unwrap()).unwrap().count();
+        assert_eq!(leftovers, 1);
+    }
+
+    #[test]
+    fn test_builder_fallback() {
+        let root = TempRoot::new();
+        let builder = crate::MachineIdBuilder::empty()
+            .source(crate::sources::MachineIdFile)
+            .source(Persisted::new("/var/lib/app/machine-id"))
+            .root(root.path());
+
+        let resolved = builder.resolve().unwrap();
+        assert_eq!(resolved.source, "persisted");
+        assert_eq!(builder.build().unwrap(), resolved.id);
+    }
+}
@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use uuid::Uuid;
+
+use super::{ensure_host_root, parse_id, read_id_file, resolve_path, IdSource};
+use crate::{error::Location, Result};
+
+/// `CTL_KERN` : `KERN_HOSTUUID`, see [sysctl(3)](https://man.freebsd.org/cgi/man.cgi?sysctl(3)).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KernHostUuid;
+
+impl IdSource for KernHostUuid {
+    fn name(&self) -> &str {
+        "kern.hostuuid"
+    }
+
+    fn read(&self, root: &Path) -> Result<Uuid> {
+        ensure_host_root(root)?;
+        parse_id(&host_uuid()?)
+    }
+
+    fn location(&self, _root: &Path) -> Option<Location> {
+        Some(Location::Sysctl("kern.hostuuid".to_owned()))
+    }
+}
+
+/// `/etc/hostid`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HostIdFile;
+
+impl IdSource for HostIdFile {
+    fn name(&self) -> &str {
+        "hostid"
+    }
+
+    fn read(&self, root: &Path) -> Result<Uuid> {
+        read_id_file(&resolve_path(root, "/etc/hostid".as_ref()))
+    }
+
+    fn location(&self, root: &Path) -> Option<Location> {
+        Some(Location::File(resolve_path(root, "/etc/hostid".as_ref())))
+    }
+}
+
+fn host_uuid() -> std::io::Result<String> {
+    const KERN_HOSTUUID: i32 = 0x24i32;
+    let vec = sysctl([libc::CTL_KERN, KERN_HOSTUUID])?;
+
+    Ok(vec
+        .into_iter()
+        .take_while(|ch| *ch != 0)
+        .map(|ch| ch as char)
+        .collect::<String>())
+}
+
+fn sysctl<const N: usize>(mib: [i32; N]) -> std::io::Result<Vec<u8>> {
+    use std::ptr;
+    let (mut m, mut n) = (mib, 0);
+    let r = unsafe {
+        libc::sysctl(
+            m.as_mut_ptr() as _,
+            m.len() as _,
+            ptr::null_mut(),
+            &mut n,
+            ptr::null_mut(),
+            0,
+        )
+    };
+
+    if r != 0 {
+        return Err(std::io::Error::from_raw_os_error(r));
+    }
+    let mut b = Vec::with_capacity(n);
+    let s = unsafe {
+        let res = libc::sysctl(
+            m.as_mut_ptr() as _,
+            m.len() as _,
+            b.as_mut_ptr() as _,
+            &mut n,
+            ptr::null_mut(),
+            0,
+        );
+        b.set_len(n);
+        res
+    };
+    if s != 0 {
+        Err(std::io::Error::from_raw_os_error(s))
+    } else {
+        Ok(b)
+    }
+}
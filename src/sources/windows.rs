@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use uuid::Uuid;
+
+use super::{ensure_host_root, parse_id, IdSource};
+use crate::{error::Location, Result};
+
+/// The `MachineGuid` value from `HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Cryptography`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MachineGuid;
+
+impl IdSource for MachineGuid {
+    fn name(&self) -> &str {
+        "registry"
+    }
+
+    fn read(&self, root: &Path) -> Result<Uuid> {
+        use winreg::{enums::HKEY_LOCAL_MACHINE, RegKey};
+
+        ensure_host_root(root)?;
+
+        let guid_str = RegKey::predef(HKEY_LOCAL_MACHINE)
+            .open_subkey("SOFTWARE\\Microsoft\\Cryptography")
+            .and_then(|key| key.get_value::<String, _>("MachineGuid"))?;
+
+        parse_id(&guid_str)
+    }
+
+    fn location(&self, _root: &Path) -> Option<Location> {
+        Some(Location::RegistryValue(
+            "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Cryptography\\MachineGuid".to_owned(),
+        ))
+    }
+}
@@ -0,0 +1,206 @@
+use std::str::FromStr;
+
+use crate::{
+    app_specific::hmac_sha256,
+    encoding::{crockford_check, crockford_check_value, crockford_encode, crockford_value},
+    error::Error,
+    MachineId,
+};
+
+const SUPPORT_CODE_KEY: &[u8] = b"yamid.support-code.v1";
+const SYMBOLS: usize = 16;
+const GROUP_LEN: usize = 4;
+
+/// A short code identifying a machine, meant to be read out over the phone or typed by hand.
+///
+/// The code is an 80-bit hash of the machine ID written as four groups of Crockford base32
+/// symbols followed by a check symbol, e.g. `TMSF-SNY5-HPHV-SZ68-G`. It cannot be turned
+/// back into the machine ID, so compare it with the code computed on the machine instead.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub struct SupportCode(u128);
+
+/// A symbol the parser read as a different one, see [`SupportCode::parse`].
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Correction {
+    /// Character index in the parsed text.
+    pub position: usize,
+    pub found: char,
+    pub read_as: char,
+}
+
+/// The result of [`SupportCode::parse`].
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ParsedSupportCode {
+    pub code: SupportCode,
+    /// Ambiguous symbols that were interpreted, empty if the text was typed exactly.
+    pub corrections: Vec<Correction>,
+}
+
+impl MachineId {
+    /// The [`SupportCode`] of this machine.
+    ///
+    /// Use it on an application-specific ID (`id.app_specific(app).support_code()`)
+    /// to get codes that cannot be correlated across vendors.
+    pub fn support_code(&self) -> SupportCode {
+        let digest = hmac_sha256(self.0.as_bytes(), &[SUPPORT_CODE_KEY]);
+        let mut bytes = [0; 16];
+        bytes[6..].copy_from_slice(&digest[..10]);
+        SupportCode(u128::from_be_bytes(bytes))
+    }
+}
+
+impl SupportCode {
+    /// Parses a code leniently: case, whitespace and the position of hyphens do not matter,
+    /// and `I`/`L` and `O` are read as `1` and `0`, reported as [`Correction`]s.
+    ///
+    /// Any other mistyped or swapped symbol is detected by the check symbol.
+    pub fn parse(s: &str) -> Result<ParsedSupportCode, Error> {
+        let invalid = |reason: String| Error::InvalidSupportCode(reason);
+
+        let symbols: Vec<(usize, char)> = s
+            .chars()
+            .enumerate()
+            .filter(|(_, ch)| *ch != '-' && !ch.is_whitespace())
+            .collect();
+        let Some(((check_position, check), data)) = symbols.split_last() else {
+            return Err(invalid("empty support code".to_owned()));
+        };
+        if data.len() != SYMBOLS {
+            return Err(invalid(format!(
+                "expected {} symbols including the check symbol, found {}",
+                SYMBOLS + 1,
+                symbols.len()
+            )));
+        }
+
+        let invalid_symbol =
+            |position, found| invalid(format!("invalid symbol `{found}` at {position}"));
+        // The check alphabet starts with the base32 one, so `crockford_check`
+        // also gives the canonical form of data symbols.
+        let correction = |position, found: char, value: u8| {
+            let read_as = crockford_check(value.into());
+            (!found.eq_ignore_ascii_case(&read_as)).then_some(Correction {
+                position,
+                found,
+                read_as,
+            })
+        };
+
+        let mut corrections = Vec::new();
+        let mut value = 0u128;
+        for &(position, found) in data {
+            let symbol = crockford_value(found).ok_or_else(|| invalid_symbol(position, found))?;
+            corrections.extend(correction(position, found, symbol));
+            value = value << 5 | u128::from(symbol);
+        }
+        let check_value =
+            crockford_check_value(*check).ok_or_else(|| invalid_symbol(*check_position, *check))?;
+        corrections.extend(correction(*check_position, *check, check_value));
+
+        if crockford_check(value) != crockford_check(check_value.into()) {
+            return Err(invalid(
+                "check symbol does not match, a symbol was mistyped".to_owned(),
+            ));
+        }
+
+        Ok(ParsedSupportCode {
+            code: SupportCode(value),
+            corrections,
+        })
+    }
+}
+
+impl std::fmt::Display for SupportCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbols = crockford_encode(self.0, SYMBOLS);
+        for (i, group) in symbols.as_bytes().chunks(GROUP_LEN).enumerate() {
+            if i > 0 {
+                f.write_str("-")?;
+            }
+            f.write_str(std::str::from_utf8(group).expect("base32 is ASCII"))?;
+        }
+        write!(f, "-{}", crockford_check(self.0))
+    }
+}
+
+impl FromStr for SupportCode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SupportCode::parse(s).map(|parsed| parsed.code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code() -> SupportCode {
+        let id: MachineId = "3d1219c7c4c5404aaa1f6d2a48adfda4".parse().unwrap();
+        id.support_code()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let code = code();
+        let text = code.to_string();
+        assert_eq!(text, "TMSF-SNY5-HPHV-SZ68-G");
+
+        let parsed = SupportCode::parse(&text).unwrap();
+        assert_eq!(parsed.code, code);
+        assert!(parsed.corrections.is_empty());
+
+        let sloppy = format!(" {} ", text.replace('-', " ").to_ascii_lowercase());
+        assert_eq!(sloppy.parse::<SupportCode>().unwrap(), code);
+
+        let other: MachineId = "0f1e2d3c4b5a69788796a5b4c3d2e1f0".parse().unwrap();
+        assert_ne!(other.support_code(), code);
+    }
+
+    #[test]
+    fn test_corrections() {
+        let code = SupportCode(1 << 75);
+        assert_eq!(code.to_string(), "1000-0000-0000-0000-8");
+
+        let parsed = SupportCode::parse("l0O0-0000-0000-0000-8").unwrap();
+        assert_eq!(parsed.code, code);
+        assert_eq!(
+            parsed.corrections,
+            [
+                Correction {
+                    position: 0,
+                    found: 'l',
+                    read_as: '1'
+                },
+                Correction {
+                    position: 2,
+                    found: 'O',
+                    read_as: '0'
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_typos_are_detected() {
+        let text = code().to_string();
+        let mut symbols: Vec<char> = text.chars().collect();
+
+        let mut substituted = symbols.clone();
+        substituted[5] = if symbols[5] == 'X' { 'Y' } else { 'X' };
+        assert!(matches!(
+            SupportCode::parse(&substituted.iter().collect::<String>()),
+            Err(Error::InvalidSupportCode(_))
+        ));
+
+        let (a, b) = (symbols[5], symbols[6]);
+        if a != b {
+            symbols.swap(5, 6);
+            assert!(SupportCode::parse(&symbols.iter().collect::<String>()).is_err());
+        }
+
+        assert!(SupportCode::parse("1000-0000-0000-000-8").is_err());
+        assert!(SupportCode::parse("1000-0000-0000-000U-8").is_err());
+        assert!(SupportCode::parse("").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+
+use uuid::Uuid;
+
+use crate::{dmi, error::Error, Result};
+
+/// Rejects placeholder and known-duplicated IDs.
+///
+/// Besides the nil UUID, all-`FF` values and other trivial patterns, cloned images
+/// commonly ship one baked-in ID to every machine. The built-in checks reject the
+/// publicly known ones, see [`KNOWN_DUPLICATES`]; add your own golden images with
+/// [`Validator::deny`] so they are reported as [`Error::SuspiciousId`] instead of
+/// merging many machines into one.
+#[derive(Debug, Clone)]
+pub struct Validator {
+    builtin: bool,
+    denylist: BTreeMap<Uuid, String>,
+}
+
+impl Default for Validator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Validator {
+    /// Creates a validator with the built-in checks.
+    pub fn new() -> Self {
+        Self {
+            builtin: true,
+            denylist: BTreeMap::new(),
+        }
+    }
+
+    /// Creates a validator that accepts any ID, unless denied explicitly.
+    pub fn permissive() -> Self {
+        Self {
+            builtin: false,
+            denylist: BTreeMap::new(),
+        }
+    }
+
+    /// Rejects `id` with the given reason.
+    pub fn deny(mut self, id: Uuid, reason: impl Into<String>) -> Self {
+        self.denylist.insert(id, reason.into());
+        self
+    }
+
+    pub fn validate(&self, id: &Uuid) -> Result<()> {
+        let reason = match self.denylist.get(id) {
+            Some(reason) => Some(reason.clone()),
+            None if self.builtin => builtin_reason(id).map(str::to_owned),
+            None => None,
+        };
+
+        match reason {
+            Some(reason) => Err(Error::SuspiciousId { value: *id, reason }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Machine IDs shipped identically to every installation of a public image,
+/// with the image they come from.
+///
+/// Only IDs whose origin is documented upstream are listed.
+pub const KNOWN_DUPLICATES: &[(Uuid, &str)] = &[(
+    // Whonix and Kicksecure set the same ID everywhere on purpose to avoid fingerprinting,
+    // see `/etc/machine-id` in the `anon-base-files` package.
+    Uuid::from_u128(0xb08dfa6083e7567a1921a715000001fb),
+    "Whonix/Kicksecure shared machine ID",
+)];
+
+fn builtin_reason(id: &Uuid) -> Option<&'static str> {
+    const SEQUENTIAL: u128 = 0x0123456789abcdef0123456789abcdef;
+
+    if let Some((_, image)) = KNOWN_DUPLICATES.iter().find(|(known, _)| known == id) {
+        return Some(image);
+    }
+
+    let bytes = id.as_bytes();
+    if id.is_nil() {
+        Some("nil UUID")
+    } else if id.is_max() {
+        Some("all bits set")
+    } else if bytes.iter().all(|b| *b == bytes[0]) {
+        Some("repeated byte pattern")
+    } else if id.as_u128() == SEQUENTIAL {
+        Some("sequential pattern")
+    } else if dmi::is_placeholder_uuid(id) {
+        Some("firmware placeholder")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_checks() {
+        let validator = Validator::new();
+
+        for suspicious in [
+            0,
+            u128::MAX,
+            0x11111111111111111111111111111111,
+            0x0123456789abcdef0123456789abcdef,
+            0x03000200_0400_0500_0006_000700080009,
+        ] {
+            let error = validator
+                .validate(&Uuid::from_u128(suspicious))
+                .unwrap_err();
+            assert!(
+                matches!(error, Error::SuspiciousId { value, .. } if value.as_u128() == suspicious)
+            );
+        }
+
+        assert!(validator
+            .validate(&Uuid::from_u128(0x3d1219c7c4c5404aaa1f6d2a48adfda4))
+            .is_ok());
+        assert!(Validator::permissive().validate(&Uuid::nil()).is_ok());
+    }
+
+    #[test]
+    fn test_known_duplicates() {
+        let validator = Validator::new();
+        for (id, image) in KNOWN_DUPLICATES {
+            match validator.validate(id) {
+                Err(Error::SuspiciousId { value, reason }) => {
+                    assert_eq!(value, *id);
+                    assert_eq!(reason, *image);
+                }
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+
+        let extended = Validator::new().deny(
+            Uuid::from_u128(0x3d1219c7c4c5404aaa1f6d2a48adfda4),
+            "golden image",
+        );
+        assert!(extended.validate(&KNOWN_DUPLICATES[0].0).is_err());
+        assert!(extended
+            .validate(&Uuid::from_u128(0x3d1219c7c4c5404aaa1f6d2a48adfda4))
+            .is_err());
+    }
+
+    #[test]
+    fn test_denylist() {
+        let golden = Uuid::from_u128(0x3d1219c7c4c5404aaa1f6d2a48adfda4);
+        let validator = Validator::permissive().deny(golden, "golden image");
+
+        match validator.validate(&golden) {
+            Err(Error::SuspiciousId { value, reason }) => {
+                assert_eq!(value, golden);
+                assert_eq!(reason, "golden image");
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+}
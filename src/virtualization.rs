@@ -0,0 +1,293 @@
+//! Virtual machine and hypervisor detection.
+//!
+//! Virtual machines deployed from the same template often share the machine ID.
+//! [`detect`] tells whether the process runs in a VM and how likely the machine ID is cloned,
+//! e.g. to decide whether to use a [`crate::fingerprint::Fingerprint`] instead.
+
+use std::path::Path;
+
+use crate::{dmi::Dmi, sources::resolve_path};
+
+/// A hypervisor recognized by [`detect`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub enum Hypervisor {
+    Kvm,
+    Qemu,
+    Vmware,
+    HyperV,
+    Xen,
+    VirtualBox,
+    AwsNitro,
+    GoogleCompute,
+    Parallels,
+    Bhyve,
+    /// The CPU reports a hypervisor that is not recognized.
+    Unknown,
+}
+
+/// How likely the machine ID is shared with other machines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum CloneRisk {
+    /// Bare metal.
+    Low,
+    /// A virtual machine with a unique DMI product UUID, which a fingerprint can mix in.
+    Medium,
+    /// A virtual machine without any hardware ID to tell clones apart.
+    High,
+}
+
+/// The result of [`detect`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Virtualization {
+    pub hypervisor: Option<Hypervisor>,
+    pub clone_risk: CloneRisk,
+}
+
+impl Virtualization {
+    pub fn is_virtual(&self) -> bool {
+        self.hypervisor.is_some()
+    }
+}
+
+/// Detects the hypervisor of the running system.
+///
+/// Checks DMI vendor strings first, since cloud providers reuse common hypervisors
+/// (AWS Nitro reports KVM), then the CPUID hypervisor leaf on x86, `/sys/hypervisor`
+/// and the `hypervisor` flag in `/proc/cpuinfo`.
+pub fn detect() -> Virtualization {
+    detect_in("/")
+}
+
+/// Same as [`detect`], but reads `/sys` and `/proc` under `root`. CPUID is only queried for `/`.
+pub fn detect_in(root: impl AsRef<Path>) -> Virtualization {
+    let root = root.as_ref();
+    let hypervisor = from_dmi(root)
+        .or_else(|| (root == Path::new("/")).then(from_cpuid).flatten())
+        .or_else(|| from_sys_hypervisor(root))
+        .or_else(|| from_cpuinfo(root));
+
+    let clone_risk = match hypervisor {
+        None => CloneRisk::Low,
+        Some(_) => match Dmi::from_root(root) {
+            Ok(Dmi {
+                product_uuid: Some(_),
+                ..
+            }) => CloneRisk::Medium,
+            _ => CloneRisk::High,
+        },
+    };
+
+    Virtualization {
+        hypervisor,
+        clone_risk,
+    }
+}
+
+fn read(root: &Path, path: &str) -> Option<String> {
+    std::fs::read_to_string(resolve_path(root, path.as_ref()))
+        .ok()
+        .map(|value| value.trim().to_owned())
+}
+
+fn from_dmi(root: &Path) -> Option<Hypervisor> {
+    const VENDORS: &[(&str, Hypervisor)] = &[
+        ("Amazon EC2", Hypervisor::AwsNitro),
+        ("Google", Hypervisor::GoogleCompute),
+        ("QEMU", Hypervisor::Qemu),
+        ("KVM", Hypervisor::Kvm),
+        ("VMware", Hypervisor::Vmware),
+        ("VMW", Hypervisor::Vmware),
+        ("innotek GmbH", Hypervisor::VirtualBox),
+        ("VirtualBox", Hypervisor::VirtualBox),
+        ("Oracle Corporation", Hypervisor::VirtualBox),
+        ("Xen", Hypervisor::Xen),
+        ("Parallels", Hypervisor::Parallels),
+        ("BHYVE", Hypervisor::Bhyve),
+    ];
+
+    let fields = ["sys_vendor", "product_name", "board_vendor", "bios_vendor"]
+        .map(|name| read(root, &format!("/sys/class/dmi/id/{name}")).unwrap_or_default());
+
+    // Hyper-V shares its vendor with Microsoft hardware, e.g. Surface devices.
+    if fields[0] == "Microsoft Corporation" && fields[1] == "Virtual Machine" {
+        return Some(Hypervisor::HyperV);
+    }
+
+    let hypervisor = fields.iter().find_map(|field| {
+        VENDORS
+            .iter()
+            .find(|(vendor, _)| field.starts_with(vendor))
+            .map(|(_, hypervisor)| *hypervisor)
+    })?;
+
+    // EC2 bare-metal instances report the same vendor, like systemd only the
+    // firmware's "virtual machine" flag tells them apart.
+    if hypervisor == Hypervisor::AwsNitro && smbios_vm_flag(root) == Some(false) {
+        return None;
+    }
+    Some(hypervisor)
+}
+
+/// The "virtual machine" bit of the SMBIOS BIOS Information structure (type 0), `None` if
+/// the structure is unreadable or too old to have it.
+fn smbios_vm_flag(root: &Path) -> Option<bool> {
+    // Offset of BIOS Characteristics Extension Byte 2, present since SMBIOS 2.4.
+    const EXTENSION_BYTE_2: usize = 0x13;
+    const VIRTUAL_MACHINE: u8 = 1 << 4;
+
+    let raw = std::fs::read(resolve_path(
+        root,
+        "/sys/firmware/dmi/entries/0-0/raw".as_ref(),
+    ))
+    .ok()?;
+    // The second byte is the length of the formatted area, strings follow.
+    let length = usize::from(*raw.get(1)?);
+    if length <= EXTENSION_BYTE_2 {
+        return None;
+    }
+    Some(raw.get(EXTENSION_BYTE_2)? & VIRTUAL_MACHINE != 0)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[allow(unused_unsafe)]
+fn from_cpuid() -> Option<Hypervisor> {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::__cpuid;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::__cpuid;
+
+    const HYPERVISOR_PRESENT: u32 = 1 << 31;
+    if unsafe { __cpuid(1) }.ecx & HYPERVISOR_PRESENT == 0 {
+        return None;
+    }
+
+    let leaf = unsafe { __cpuid(0x4000_0000) };
+    let mut signature = [0u8; 12];
+    signature[..4].copy_from_slice(&leaf.ebx.to_le_bytes());
+    signature[4..8].copy_from_slice(&leaf.ecx.to_le_bytes());
+    signature[8..].copy_from_slice(&leaf.edx.to_le_bytes());
+
+    Some(match &signature {
+        b"KVMKVMKVM\0\0\0" => Hypervisor::Kvm,
+        b"TCGTCGTCGTCG" => Hypervisor::Qemu,
+        b"Microsoft Hv" => Hypervisor::HyperV,
+        b"VMwareVMware" => Hypervisor::Vmware,
+        b"XenVMMXenVMM" => Hypervisor::Xen,
+        b"VBoxVBoxVBox" => Hypervisor::VirtualBox,
+        b"bhyve bhyve " => Hypervisor::Bhyve,
+        b" lrpepyh  vr" => Hypervisor::Parallels,
+        _ => Hypervisor::Unknown,
+    })
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+fn from_cpuid() -> Option<Hypervisor> {
+    None
+}
+
+fn from_sys_hypervisor(root: &Path) -> Option<Hypervisor> {
+    match read(root, "/sys/hypervisor/type")?.as_str() {
+        "xen" => Some(Hypervisor::Xen),
+        _ => Some(Hypervisor::Unknown),
+    }
+}
+
+fn from_cpuinfo(root: &Path) -> Option<Hypervisor> {
+    let cpuinfo = read(root, "/proc/cpuinfo")?;
+    cpuinfo
+        .lines()
+        .filter(|line| line.starts_with("flags"))
+        .any(|line| line.split_whitespace().any(|flag| flag == "hypervisor"))
+        .then_some(Hypervisor::Unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempRoot;
+
+    #[test]
+    fn test_detect() {
+        let root = TempRoot::new();
+        assert_eq!(
+            detect_in(root.path()),
+            Virtualization {
+                hypervisor: None,
+                clone_risk: CloneRisk::Low
+            }
+        );
+
+        root.write(
+            "proc/cpuinfo",
+            "processor\t: 0\nflags\t\t: fpu vme hypervisor\n",
+        );
+        assert_eq!(
+            detect_in(root.path()),
+            Virtualization {
+                hypervisor: Some(Hypervisor::Unknown),
+                clone_risk: CloneRisk::High
+            }
+        );
+
+        root.write("sys/class/dmi/id/sys_vendor", "Amazon EC2\n");
+        root.write(
+            "sys/class/dmi/id/product_uuid",
+            "ec2a1b2c-3d4e-5f60-7182-93a4b5c6d7e8\n",
+        );
+        assert_eq!(
+            detect_in(root.path()),
+            Virtualization {
+                hypervisor: Some(Hypervisor::AwsNitro),
+                clone_risk: CloneRisk::Medium
+            }
+        );
+
+        // EC2 bare metal: the firmware does not flag a virtual machine.
+        let mut bios_information = vec![0u8; 0x18];
+        bios_information[1] = 0x18;
+        root.write("sys/firmware/dmi/entries/0-0/raw", &bios_information);
+        root.write("proc/cpuinfo", "processor\t: 0\nflags\t\t: fpu vme\n");
+        assert_eq!(
+            detect_in(root.path()),
+            Virtualization {
+                hypervisor: None,
+                clone_risk: CloneRisk::Low
+            }
+        );
+
+        bios_information[0x13] = 1 << 4;
+        root.write("sys/firmware/dmi/entries/0-0/raw", &bios_information);
+        assert_eq!(
+            detect_in(root.path()).hypervisor,
+            Some(Hypervisor::AwsNitro)
+        );
+
+        root.write(
+            "proc/cpuinfo",
+            "processor\t: 0\nflags\t\t: fpu vme hypervisor\n",
+        );
+
+        root.write("sys/class/dmi/id/sys_vendor", "Microsoft Corporation\n");
+        root.write("sys/class/dmi/id/product_name", "Virtual Machine\n");
+        assert_eq!(detect_in(root.path()).hypervisor, Some(Hypervisor::HyperV));
+
+        root.write("sys/class/dmi/id/product_name", "Surface Laptop\n");
+        assert_eq!(detect_in(root.path()).hypervisor, Some(Hypervisor::Unknown));
+    }
+
+    #[test]
+    fn test_detect_running_system() {
+        let virtualization = detect();
+        println!("Virtualization: {virtualization:?}");
+        assert_eq!(
+            virtualization.is_virtual(),
+            virtualization.clone_risk > CloneRisk::Low
+        );
+    }
+}
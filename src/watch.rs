@@ -0,0 +1,548 @@
+//! Notifications about machine ID changes, e.g. after `systemd-machine-id-setup`
+//! or sysprep regenerated it.
+//!
+//! ```no_run
+//! let watcher = yamid::watch::watch(|change| {
+//!     println!("machine ID changed from {:?} to {}", change.old, change.new);
+//! })?;
+//! # Ok::<(), yamid::error::Error>(())
+//! ```
+
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{MachineId, MachineIdBuilder, Result};
+
+/// A machine ID change seen by a [`Watcher`].
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Change {
+    /// `None` if no ID could be read when the watcher started.
+    pub old: Option<MachineId>,
+    pub new: MachineId,
+}
+
+/// Watches for changes of the machine ID [`MachineIdBuilder::new`] resolves,
+/// calling `callback` on a background thread for every change.
+pub fn watch(callback: impl FnMut(Change) + Send + 'static) -> Result<Watcher> {
+    Watch::new().spawn(callback)
+}
+
+/// Like [`watch`], but delivers the changes through a channel.
+pub fn channel() -> Result<(Watcher, mpsc::Receiver<Change>)> {
+    Watch::new().channel()
+}
+
+/// Configures a [`Watcher`].
+///
+/// On Linux the watcher is notified by inotify when a file the sources read changes,
+/// and polls additionally to catch changes inotify does not report, such as new bind mounts.
+/// Elsewhere, or when inotify is unavailable (e.g. `max_user_instances` is exhausted),
+/// it polls only.
+pub struct Watch {
+    builder: MachineIdBuilder,
+    interval: Duration,
+}
+
+impl Default for Watch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Watch {
+    pub fn new() -> Self {
+        Self {
+            builder: MachineIdBuilder::new(),
+            interval: Duration::from_secs(10),
+        }
+    }
+
+    /// Watches the ID this builder resolves instead.
+    pub fn builder(mut self, builder: MachineIdBuilder) -> Self {
+        self.builder = builder;
+        self
+    }
+
+    /// Sets how often the ID is polled, 10 seconds by default.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Starts watching, calling `callback` on a background thread for every change.
+    ///
+    /// Changes also invalidate [`MachineId::cached`]. IDs that cannot be read, e.g. while
+    /// the file is being rewritten, are ignored until a valid one appears.
+    pub fn spawn(self, mut callback: impl FnMut(Change) + Send + 'static) -> Result<Watcher> {
+        self.spawn_until(move |change| {
+            callback(change);
+            true
+        })
+    }
+
+    /// Starts watching, delivering the changes through a channel.
+    ///
+    /// The watcher stops once the receiver is dropped and the next change is seen.
+    pub fn channel(self) -> Result<(Watcher, mpsc::Receiver<Change>)> {
+        let (sender, receiver) = mpsc::channel();
+        let watcher = self.spawn_until(move |change| sender.send(change).is_ok())?;
+        Ok((watcher, receiver))
+    }
+
+    fn spawn_until(self, callback: impl FnMut(Change) -> bool + Send + 'static) -> Result<Watcher> {
+        let events = Events::new(&self.builder.files());
+        self.spawn_with(events, callback)
+    }
+
+    fn spawn_with(
+        self,
+        events: Events,
+        mut callback: impl FnMut(Change) -> bool + Send + 'static,
+    ) -> Result<Watcher> {
+        let Watch { builder, interval } = self;
+        let stop = Stop::new(&events)?;
+        let stopped = stop.stopped();
+        let mut current = builder.build().ok();
+
+        let thread = thread::Builder::new()
+            .name("yamid-watch".to_owned())
+            .spawn(move || {
+                while events.wait(interval) && !stopped.load(Ordering::Acquire) {
+                    let Ok(new) = builder.build() else {
+                        continue;
+                    };
+                    if current == Some(new) {
+                        continue;
+                    }
+
+                    crate::cache::invalidate();
+                    let old = current.replace(new);
+                    if !callback(Change { old, new }) {
+                        break;
+                    }
+                }
+            })?;
+
+        Ok(Watcher {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+/// A running watch, stopped when dropped.
+pub struct Watcher {
+    stop: Stop,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Watcher {
+    /// Stops watching and waits for the background thread to finish.
+    pub fn stop(mut self) {
+        self.stop.stop(self.thread.as_ref());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop.stop(self.thread.as_ref());
+    }
+}
+
+/// What wakes the watcher thread up.
+enum Events {
+    #[cfg(target_os = "linux")]
+    Inotify(linux::Events),
+    Polling(polling::Events),
+}
+
+impl Events {
+    fn new(files: &[PathBuf]) -> Self {
+        #[cfg(target_os = "linux")]
+        if let Ok(events) = linux::Events::new(files) {
+            return Events::Inotify(events);
+        }
+        Events::Polling(polling::Events::new(files))
+    }
+
+    /// Waits for a change or the poll interval, `false` once stopped.
+    fn wait(&self, interval: Duration) -> bool {
+        match self {
+            #[cfg(target_os = "linux")]
+            Events::Inotify(events) => events.wait(interval),
+            Events::Polling(events) => events.wait(interval),
+        }
+    }
+}
+
+enum Stop {
+    #[cfg(target_os = "linux")]
+    Inotify(linux::Stop),
+    Polling(polling::Stop),
+}
+
+impl Stop {
+    fn new(events: &Events) -> std::io::Result<Self> {
+        Ok(match events {
+            #[cfg(target_os = "linux")]
+            Events::Inotify(events) => Stop::Inotify(linux::Stop::new(events)?),
+            Events::Polling(events) => Stop::Polling(polling::Stop::new(events)?),
+        })
+    }
+
+    fn stopped(&self) -> Arc<AtomicBool> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Stop::Inotify(stop) => stop.stopped.clone(),
+            Stop::Polling(stop) => stop.stopped.clone(),
+        }
+    }
+
+    fn stop(&self, thread: Option<&thread::JoinHandle<()>>) {
+        match self {
+            #[cfg(target_os = "linux")]
+            Stop::Inotify(stop) => stop.stop(thread),
+            Stop::Polling(stop) => stop.stop(thread),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        collections::{HashMap, HashSet},
+        ffi::{CString, OsString},
+        io,
+        os::{
+            fd::{AsRawFd, FromRawFd, OwnedFd},
+            unix::ffi::{OsStrExt, OsStringExt},
+        },
+        path::PathBuf,
+        sync::{atomic::AtomicBool, atomic::Ordering, Arc},
+        thread::JoinHandle,
+        time::Duration,
+    };
+
+    fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// inotify watches on the directories of the watched files, as machine ID files
+    /// are usually replaced by renaming rather than rewritten.
+    pub(super) struct Events {
+        inotify: OwnedFd,
+        wake: Arc<OwnedFd>,
+        /// File names of interest per watch descriptor.
+        watches: HashMap<libc::c_int, HashSet<OsString>>,
+    }
+
+    impl Events {
+        pub(super) fn new(files: &[PathBuf]) -> io::Result<Self> {
+            // SAFETY: plain syscalls, the descriptors are owned right away.
+            let inotify = unsafe {
+                OwnedFd::from_raw_fd(check(libc::inotify_init1(
+                    libc::IN_NONBLOCK | libc::IN_CLOEXEC,
+                ))?)
+            };
+            let wake = unsafe {
+                OwnedFd::from_raw_fd(check(libc::eventfd(
+                    0,
+                    libc::EFD_NONBLOCK | libc::EFD_CLOEXEC,
+                ))?)
+            };
+
+            let mut watches: HashMap<_, HashSet<_>> = HashMap::new();
+            for file in files {
+                let (Some(dir), Some(name)) = (file.parent(), file.file_name()) else {
+                    continue;
+                };
+                let Ok(dir) = CString::new(dir.as_os_str().as_bytes()) else {
+                    continue;
+                };
+                let mask = libc::IN_CLOSE_WRITE
+                    | libc::IN_CREATE
+                    | libc::IN_DELETE
+                    | libc::IN_MOVED_FROM
+                    | libc::IN_MOVED_TO
+                    | libc::IN_ATTRIB;
+                // SAFETY: `dir` is a valid C string.
+                // Missing directories, like `/var/lib/dbus` without dbus, are covered by polling.
+                if let Ok(wd) = check(unsafe {
+                    libc::inotify_add_watch(inotify.as_raw_fd(), dir.as_ptr(), mask)
+                }) {
+                    watches.entry(wd).or_default().insert(name.to_owned());
+                }
+            }
+
+            Ok(Self {
+                inotify,
+                wake: Arc::new(wake),
+                watches,
+            })
+        }
+
+        /// Waits for a relevant file event or the poll interval, `false` once stopped.
+        pub(super) fn wait(&self, interval: Duration) -> bool {
+            let timeout = interval.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+            loop {
+                let mut fds = [
+                    libc::pollfd {
+                        fd: self.inotify.as_raw_fd(),
+                        events: libc::POLLIN,
+                        revents: 0,
+                    },
+                    libc::pollfd {
+                        fd: self.wake.as_raw_fd(),
+                        events: libc::POLLIN,
+                        revents: 0,
+                    },
+                ];
+                // SAFETY: `fds` is a valid array of two pollfds.
+                match check(unsafe { libc::poll(fds.as_mut_ptr(), 2, timeout) }) {
+                    Ok(0) => return true,
+                    Ok(_) if fds[1].revents != 0 => return false,
+                    Ok(_) => {
+                        if self.drain() {
+                            return true;
+                        }
+                    }
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                    Err(_) => return false,
+                }
+            }
+        }
+
+        /// Reads all pending events, reporting whether any concerns a watched file.
+        fn drain(&self) -> bool {
+            const HEADER: usize = std::mem::size_of::<libc::inotify_event>();
+
+            let mut relevant = false;
+            let mut buffer = [0u8; 4096];
+            loop {
+                // SAFETY: reading into a buffer of the given length.
+                let len = unsafe {
+                    libc::read(
+                        self.inotify.as_raw_fd(),
+                        buffer.as_mut_ptr().cast(),
+                        buffer.len(),
+                    )
+                };
+                if len <= 0 {
+                    return relevant;
+                }
+
+                let mut events = &buffer[..len as usize];
+                while events.len() >= HEADER {
+                    // SAFETY: the kernel writes whole events, `read_unaligned` handles alignment.
+                    let event: libc::inotify_event =
+                        unsafe { std::ptr::read_unaligned(events.as_ptr().cast()) };
+                    let name_len = event.len as usize;
+                    let name = &events[HEADER..HEADER + name_len];
+                    let name = name.split(|b| *b == 0).next().unwrap_or_default();
+                    relevant |= event.mask & libc::IN_Q_OVERFLOW != 0
+                        || self.watches.get(&event.wd).is_some_and(|names| {
+                            names.contains(&OsString::from_vec(name.to_vec()))
+                        });
+                    events = &events[HEADER + name_len..];
+                }
+            }
+        }
+    }
+
+    pub(super) struct Stop {
+        pub(super) stopped: Arc<AtomicBool>,
+        wake: Arc<OwnedFd>,
+    }
+
+    impl Stop {
+        pub(super) fn new(events: &Events) -> io::Result<Self> {
+            Ok(Self {
+                stopped: Arc::new(AtomicBool::new(false)),
+                wake: events.wake.clone(),
+            })
+        }
+
+        pub(super) fn stop(&self, _thread: Option<&JoinHandle<()>>) {
+            self.stopped.store(true, Ordering::Release);
+            let one = 1u64.to_ne_bytes();
+            // SAFETY: writing 8 bytes to an eventfd.
+            unsafe { libc::write(self.wake.as_raw_fd(), one.as_ptr().cast(), one.len()) };
+        }
+    }
+}
+
+mod polling {
+    use std::{
+        io,
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    pub(super) struct Events;
+
+    impl Events {
+        pub(super) fn new(_files: &[PathBuf]) -> Self {
+            Self
+        }
+
+        /// Waits for the poll interval, woken early by [`Stop::stop`].
+        pub(super) fn wait(&self, interval: Duration) -> bool {
+            thread::park_timeout(interval);
+            true
+        }
+    }
+
+    pub(super) struct Stop {
+        pub(super) stopped: Arc<AtomicBool>,
+    }
+
+    impl Stop {
+        pub(super) fn new(_events: &Events) -> io::Result<Self> {
+            Ok(Self {
+                stopped: Arc::new(AtomicBool::new(false)),
+            })
+        }
+
+        pub(super) fn stop(&self, thread: Option<&thread::JoinHandle<()>>) {
+            self.stopped.store(true, Ordering::Release);
+            if let Some(thread) = thread {
+                thread.thread().unpark();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::AtomicUsize, Arc, Mutex},
+        time::Instant,
+    };
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::sources;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn test_polling() {
+        let value = Arc::new(Mutex::new(None));
+        let reads = Arc::new(AtomicUsize::new(0));
+        let source = {
+            let value = value.clone();
+            let reads = reads.clone();
+            sources::from_fn("test", move || {
+                reads.fetch_add(1, Ordering::SeqCst);
+                value
+                    .lock()
+                    .unwrap()
+                    .map(Uuid::from_u128)
+                    .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound).into())
+            })
+        };
+
+        // Polling only, as when inotify is unavailable.
+        let (sender, changes) = mpsc::channel();
+        let watcher = Watch::new()
+            .builder(MachineIdBuilder::empty().source(source))
+            .interval(Duration::from_millis(10))
+            .spawn_with(Events::Polling(polling::Events), move |change| {
+                sender.send(change).is_ok()
+            })
+            .unwrap();
+
+        let first = MachineId(Uuid::from_u128(0x3d1219c7c4c5404aaa1f6d2a48adfda4));
+        *value.lock().unwrap() = Some(first.0.as_u128());
+        assert_eq!(
+            changes.recv_timeout(TIMEOUT).unwrap(),
+            Change {
+                old: None,
+                new: first
+            }
+        );
+
+        // Unreadable IDs are skipped.
+        *value.lock().unwrap() = None;
+        let failed_from = reads.load(Ordering::SeqCst) + 1;
+        let deadline = Instant::now() + TIMEOUT;
+        while reads.load(Ordering::SeqCst) <= failed_from + 1 {
+            assert!(Instant::now() < deadline, "the source is not polled");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(
+            changes.recv_timeout(Duration::from_millis(50)),
+            Err(mpsc::RecvTimeoutError::Timeout)
+        );
+
+        let second = MachineId(Uuid::from_u128(0x0f1e2d3c4b5a69788796a5b4c3d2e1f0));
+        *value.lock().unwrap() = Some(second.0.as_u128());
+        assert_eq!(
+            changes.recv_timeout(TIMEOUT).unwrap(),
+            Change {
+                old: Some(first),
+                new: second
+            }
+        );
+
+        watcher.stop();
+        assert!(changes.recv().is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_inotify() {
+        let root = crate::test_util::TempRoot::new();
+        root.write("etc/machine-id", "3d1219c7c4c5404aaa1f6d2a48adfda4\n");
+
+        let (_watcher, changes) = Watch::new()
+            .builder(
+                MachineIdBuilder::empty()
+                    .source(sources::MachineIdFile)
+                    .root(root.path()),
+            )
+            .interval(Duration::from_secs(3600))
+            .channel()
+            .unwrap();
+
+        root.write("etc/unrelated", "x");
+        root.write("etc/machine-id.tmp", "0f1e2d3c4b5a69788796a5b4c3d2e1f0\n");
+        std::fs::rename(
+            root.path().join("etc/machine-id.tmp"),
+            root.path().join("etc/machine-id"),
+        )
+        .unwrap();
+
+        let change = changes.recv_timeout(TIMEOUT).unwrap();
+        assert_eq!(
+            change.old,
+            Some(MachineId(Uuid::from_u128(
+                0x3d1219c7c4c5404aaa1f6d2a48adfda4
+            )))
+        );
+        assert_eq!(
+            change.new,
+            MachineId(Uuid::from_u128(0x0f1e2d3c4b5a69788796a5b4c3d2e1f0))
+        );
+    }
+}